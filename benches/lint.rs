@@ -0,0 +1,164 @@
+//! Measures how long linting and formatting take per file, run with `cargo bench`.
+//!
+//! The rules (`run_checks`) and the formatter (`format_code`) run in-process on a fixed script,
+//! so their timings don't include reading files or starting the linter. A last run lints a
+//! generated corpus of `FOLI_BENCH_FILES` files (3000 by default), or the `.gd` files below
+//! `FOLI_BENCH_CORPUS` if it's set, end to end on all CPUs and with `--no-cache`.
+
+use std::{
+    hint::black_box,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use gdscript_foli::{config::RuleConfigs, format};
+
+/// How often the in-process benchmarks run the fixed script.
+const ITERATIONS: u32 = 1000;
+
+const SCRIPT: &str = r#"class_name Enemy{index}
+extends CharacterBody2D
+## An enemy that chases the player.
+
+signal died(position: Vector2)
+
+enum State { IDLE, CHASE, ATTACK }
+
+const SPEED := 120.0
+const Bullet = preload("res://bullet.tscn")
+
+@export var health: int = 3
+var state := State.IDLE
+@onready var sprite: Sprite2D = $Sprite
+
+
+func _ready() -> void:
+	sprite.modulate = Color.RED
+
+
+func _physics_process(delta: float) -> void:
+	if state == State.IDLE:
+		velocity = Vector2.ZERO
+	elif state == State.CHASE:
+		var target := get_tree().get_first_node_in_group("player") as Node2D
+		if target and global_position.distance_to(target.global_position) > 10.0:
+			velocity = global_position.direction_to(target.global_position) * SPEED * delta
+	match health:
+		0, 1:
+			sprite.visible = not sprite.visible
+		_:
+			pass
+	move_and_slide()
+
+
+func hit(damage: int) -> void:
+	health -= damage
+	for i in range(damage):
+		print("hit ", i)
+	if health <= 0:
+		died.emit(global_position)
+		queue_free()
+
+
+class Drop:
+	var amount := 1
+
+	func collect(into: Array) -> void:
+		into.append(amount)
+"#;
+
+fn generate_corpus(dir: &Path, files: usize) {
+    std::fs::create_dir_all(dir).expect("create corpus directory");
+    for index in 0..files {
+        let script = SCRIPT.replace("{index}", &index.to_string());
+        std::fs::write(dir.join(format!("enemy{index}.gd")), script).expect("write script");
+    }
+}
+
+fn count_scripts(dir: &Path) -> usize {
+    std::fs::read_dir(dir)
+        .expect("read corpus directory")
+        .map(|entry| entry.expect("directory entry").path())
+        .map(|path| match path.is_dir() {
+            true => count_scripts(&path),
+            false => usize::from(path.extension().is_some_and(|ext| ext == "gd")),
+        })
+        .sum()
+}
+
+/// The average time of a run, after a run that warms up.
+fn measure(mut run: impl FnMut()) -> Duration {
+    run();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn lint(corpus: &Path) -> Duration {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_gdscript-foli"));
+    command
+        .current_dir(corpus)
+        .args(["lint", ".", "--no-default-excludes", "--no-cache"]);
+
+    let start = Instant::now();
+    let output = command.output().expect("run gdscript-foli");
+    let elapsed = start.elapsed();
+    // Exit code 3 means the tool itself failed, the others are about the diagnostics
+    assert_ne!(output.status.code(), Some(3), "linting failed: {output:?}");
+    elapsed
+}
+
+fn main() {
+    let source = Arc::<str>::from(SCRIPT.replace("{index}", "0"));
+    let mut parser = gdscript_foli::new_parser().expect("create parser");
+    let tree = parser.parse(source.as_bytes(), None).expect("parse script");
+    let rules = RuleConfigs::default();
+    let path = Path::new("enemy0.gd");
+    let elapsed = measure(|| {
+        black_box(gdscript_foli::run_checks(
+            tree.root_node(),
+            Arc::clone(&source),
+            &rules,
+            path,
+        ));
+    });
+    println!("{:>10}: {elapsed:>8.2?} per file", "rules");
+
+    let options = format::FormatOptions::default();
+    let elapsed = measure(|| {
+        black_box(format::format_code(&mut parser, &source, &options).expect("format script"));
+    });
+    println!("{:>10}: {elapsed:>8.2?} per file", "format");
+
+    let (corpus, generated) = match std::env::var_os("FOLI_BENCH_CORPUS") {
+        Some(corpus) => (PathBuf::from(corpus), false),
+        None => {
+            let files = std::env::var("FOLI_BENCH_FILES")
+                .ok()
+                .map(|files| files.parse().expect("FOLI_BENCH_FILES is a number"))
+                .unwrap_or(3000);
+            let dir = std::env::temp_dir().join(format!("foli-bench-{}", std::process::id()));
+            generate_corpus(&dir, files);
+            (dir, true)
+        }
+    };
+    let files = count_scripts(&corpus);
+    println!("Linting {files} files in {}", corpus.display());
+
+    // The first run warms up the file system cache
+    lint(&corpus);
+    let elapsed = lint(&corpus);
+    println!(
+        "{:>10}: {:>8.2?} per file, {elapsed:.2?} total",
+        "lint",
+        elapsed / files.max(1) as u32,
+    );
+
+    if generated {
+        std::fs::remove_dir_all(&corpus).expect("remove corpus directory");
+    }
+}
@@ -0,0 +1,271 @@
+//! Baselines make it possible to adopt the linter (or a new rule) in a project with many
+//! existing diagnostics: `--write-baseline` records them and `--baseline` only reports new ones.
+//!
+//! Diagnostics are recorded by path, code and fingerprint (see
+//! [`Diagnostic::all`](crate::output::Diagnostic::all)), so they are still recognized after
+//! code above them changed. Paths are relative to the baseline file, so it works no matter where
+//! the linter is run from.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use miette::Report;
+use serde::{Deserialize, Serialize};
+
+use crate::{files, output::Diagnostic};
+
+/// Used if `--baseline` or `--write-baseline` are given without a path, relative to the
+/// project root.
+pub const DEFAULT_PATH: &str = "foli-baseline.json";
+
+/// Bumped if old baselines can't be read anymore.
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Entry {
+    pub path: String,
+    pub code: String,
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BaselineFile {
+    version: u32,
+    entries: Vec<Entry>,
+}
+
+/// The path of a file in the baseline, `dir` is the directory of the baseline file.
+fn relative_path(dir: &Path, path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    path.strip_prefix(dir)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// The entries of the reports of a file.
+fn entries(dir: &Path, path: &Path, source: &str, reports: &[Report]) -> Vec<Entry> {
+    let path = relative_path(dir, path);
+    Diagnostic::all(Path::new(&path), source, reports)
+        .into_iter()
+        .map(|diagnostic| Entry {
+            path: diagnostic.path,
+            code: diagnostic.code,
+            fingerprint: diagnostic.fingerprint,
+        })
+        .collect()
+}
+
+fn dir_of(path: &Path) -> PathBuf {
+    std::path::absolute(path)
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+}
+
+fn read(path: &Path) -> anyhow::Result<BaselineFile> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read the baseline {}", path.display()))?;
+    let file: BaselineFile = serde_json::from_str(&content)
+        .with_context(|| format!("failed to parse the baseline {}", path.display()))?;
+    if file.version != VERSION {
+        anyhow::bail!(
+            "the baseline {} has version {}, expected {VERSION}, write it again with `--write-baseline`",
+            path.display(),
+            file.version
+        );
+    }
+    Ok(file)
+}
+
+/// A baseline that's matched against the reports of a lint run.
+#[derive(Debug)]
+pub struct Baseline {
+    dir: PathBuf,
+    entries: HashSet<Entry>,
+    /// The entries that still have a diagnostic.
+    matched: HashSet<Entry>,
+    /// The paths of the linted files, only their entries can be fixed.
+    linted: HashSet<String>,
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = read(path)?;
+        Ok(Self {
+            dir: dir_of(path),
+            entries: file.entries.into_iter().collect(),
+            matched: HashSet::new(),
+            linted: HashSet::new(),
+        })
+    }
+
+    /// Removes the reports that are in the baseline.
+    pub fn filter(&mut self, path: &Path, source: &str, reports: Vec<Report>) -> Vec<Report> {
+        self.linted.insert(relative_path(&self.dir, path));
+        let entries = entries(&self.dir, path, source, &reports);
+        reports
+            .into_iter()
+            .zip(entries)
+            .filter_map(|(report, entry)| {
+                if self.entries.contains(&entry) {
+                    self.matched.insert(entry);
+                    None
+                } else {
+                    Some(report)
+                }
+            })
+            .collect()
+    }
+
+    /// The entries of the linted files that don't have a diagnostic anymore.
+    pub fn fixed(&self) -> Vec<&Entry> {
+        let mut fixed = self
+            .entries
+            .iter()
+            .filter(|entry| self.linted.contains(&entry.path) && !self.matched.contains(entry))
+            .collect::<Vec<_>>();
+        fixed.sort();
+        fixed
+    }
+}
+
+/// Collects the entries of a lint run for `--write-baseline`.
+#[derive(Debug)]
+pub struct BaselineWriter {
+    path: PathBuf,
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    /// The paths of the linted files, the entries of other files are kept.
+    linted: HashSet<String>,
+}
+
+impl BaselineWriter {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            dir: dir_of(path),
+            entries: Vec::new(),
+            linted: HashSet::new(),
+        }
+    }
+
+    pub fn record(&mut self, path: &Path, source: &str, reports: &[Report]) {
+        self.linted.insert(relative_path(&self.dir, path));
+        self.entries
+            .extend(entries(&self.dir, path, source, reports));
+    }
+
+    /// Writes the baseline, returns the number of entries of the linted files.
+    ///
+    /// The entries of the files that weren't linted are kept from the existing baseline, so
+    /// linting a part of the project doesn't drop the rest. A baseline that can't be read is
+    /// replaced.
+    pub fn save(mut self) -> anyhow::Result<usize> {
+        let count = self.entries.len();
+        if let Ok(existing) = read(&self.path) {
+            self.entries.extend(
+                existing
+                    .entries
+                    .into_iter()
+                    .filter(|entry| !self.linted.contains(&entry.path)),
+            );
+        }
+        // Sorted, so the baseline only changes where diagnostics do
+        self.entries.sort();
+        let file = BaselineFile {
+            version: VERSION,
+            entries: self.entries,
+        };
+        let content = serde_json::to_string_pretty(&file).expect("the baseline is serializable");
+        files::write_atomic(&self.path, &(content + "\n"))
+            .with_context(|| format!("failed to write the baseline {}", self.path.display()))?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn reports(source: &Arc<str>) -> Vec<Report> {
+        source
+            .match_indices("print")
+            .map(|(offset, _)| {
+                miette::miette!(
+                    code = "no-print",
+                    labels = vec![miette::LabeledSpan::at(offset..offset + 5, "here")],
+                    "print",
+                )
+                .with_source_code(Arc::clone(source))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_new_diagnostics_are_reported() {
+        let dir = std::env::temp_dir().join(format!("foli-baseline-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let baseline_path = dir.join(DEFAULT_PATH);
+        let (a, b) = (dir.join("a.gd"), dir.join("b.gd"));
+
+        let old_a = Arc::<str>::from("func f():\n\tprint(1)\n\tprint(2)\n");
+        let old_b = Arc::<str>::from("func g():\n\tprint(1)\n");
+        let mut writer = BaselineWriter::new(&baseline_path);
+        writer.record(&a, &old_a, &reports(&old_a));
+        writer.record(&b, &old_b, &reports(&old_b));
+        assert_eq!(writer.save().unwrap(), 3);
+
+        let mut baseline = Baseline::load(&baseline_path).unwrap();
+        let new_a = Arc::<str>::from("var x := 1\n\nfunc f():\n\tprint(1)\n\tprint(3)\n");
+        let kept = baseline.filter(&a, &new_a, reports(&new_a));
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].labels().unwrap().next().unwrap().offset(), 33);
+
+        // `b.gd` wasn't linted, so its entry isn't fixed
+        let fixed = baseline.fixed();
+        assert_eq!(fixed.len(), 1);
+        assert_eq!(
+            (fixed[0].path.as_str(), fixed[0].code.as_str()),
+            ("a.gd", "no-print")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writing_keeps_the_entries_of_other_files() {
+        let dir = std::env::temp_dir().join(format!("foli-baseline-write-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let baseline_path = dir.join(DEFAULT_PATH);
+        let (a, b) = (dir.join("a.gd"), dir.join("b.gd"));
+
+        let old_a = Arc::<str>::from("func f():\n\tprint(1)\n\tprint(2)\n");
+        let old_b = Arc::<str>::from("func g():\n\tprint(1)\n");
+        let mut writer = BaselineWriter::new(&baseline_path);
+        writer.record(&a, &old_a, &reports(&old_a));
+        writer.record(&b, &old_b, &reports(&old_b));
+        assert_eq!(writer.save().unwrap(), 3);
+
+        // Only `a.gd` is linted again, `b.gd` keeps its entry
+        let new_a = Arc::<str>::from("func f():\n\tprint(1)\n");
+        let mut writer = BaselineWriter::new(&baseline_path);
+        writer.record(&a, &new_a, &reports(&new_a));
+        assert_eq!(writer.save().unwrap(), 1);
+
+        let file = read(&baseline_path).unwrap();
+        let paths = file
+            .entries
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["a.gd", "b.gd"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
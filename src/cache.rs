@@ -0,0 +1,233 @@
+//! Remembers the diagnostics of files that didn't change since the last run.
+//!
+//! The cache lives in `.foli_cache/` next to `foli.toml` (or in the working directory) and has
+//! one entry per file. An entry is only used if its key matches, the key covers everything the
+//! checks look at: the tool version, the effective rule settings, the path and the content.
+//!
+//! There are no cross-file rules: every rule only looks at the script it checks and its path, so
+//! the diagnostics of a file can't go stale when another file changes and there are no dependents
+//! to invalidate. A rule that reads other files has to add them to [`Cache::key`].
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use miette::{MietteDiagnostic, Report};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RuleConfigs,
+    files,
+    lint::fix::{self, Fix, WithFix},
+    output::stable_hash,
+};
+
+/// The directory the cache is stored in, relative to the project root.
+pub const CACHE_DIR: &str = ".foli_cache";
+const CACHE_FILE: &str = "lint.json";
+
+/// A report that can be stored and turned back into an identical report.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedReport {
+    diagnostic: MietteDiagnostic,
+    fix: Option<Fix>,
+}
+
+impl CachedReport {
+    fn new(report: &Report) -> Self {
+        let diagnostic = MietteDiagnostic {
+            message: report.to_string(),
+            code: report.code().map(|code| code.to_string()),
+            severity: report.severity(),
+            help: report.help().map(|help| help.to_string()),
+            url: report.url().map(|url| url.to_string()),
+            labels: report.labels().map(Iterator::collect),
+        };
+        Self {
+            diagnostic,
+            fix: fix::fix_of(report).cloned(),
+        }
+    }
+
+    fn to_report(&self, source: &Arc<str>) -> Report {
+        let report = Report::new(self.diagnostic.clone()).with_source_code(Arc::clone(source));
+        match &self.fix {
+            Some(fix) => report.with_fix(fix.clone()),
+            None => report,
+        }
+    }
+}
+
+/// The reports of a file, ready to be stored.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    key: u64,
+    reports: Vec<CachedReport>,
+}
+
+impl Entry {
+    pub fn new(key: u64, reports: &[Report]) -> Self {
+        Self {
+            key,
+            reports: reports.iter().map(CachedReport::new).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    entries: HashMap<PathBuf, Entry>,
+}
+
+/// The cached diagnostics of a project.
+///
+/// Lookups are read-only so the cache can be shared between threads, new entries are collected
+/// by the caller and added with [`Cache::insert`] afterwards.
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    file: CacheFile,
+    changed: bool,
+}
+
+impl Cache {
+    /// Loads the cache of the project at `root`, a missing or broken cache is treated as empty.
+    pub fn load(root: &Path) -> Self {
+        let dir = root.join(CACHE_DIR);
+        let file = std::fs::read_to_string(dir.join(CACHE_FILE))
+            .ok()
+            .and_then(|content| match serde_json::from_str(&content) {
+                Ok(file) => Some(file),
+                Err(err) => {
+                    tracing::warn!("ignoring the broken cache in {}: {err}", dir.display());
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            dir,
+            file,
+            changed: false,
+        }
+    }
+
+    /// Identifies a version of a file together with the settings it's checked with.
+    ///
+    /// Stable across Rust versions, so rebuilding the linter doesn't invalidate the cache.
+    pub fn key(rules: &RuleConfigs, path: &Path, content: &str) -> u64 {
+        let rules = serde_json::to_string(rules).expect("rule settings are serializable");
+        stable_hash(&[
+            env!("CARGO_PKG_VERSION"),
+            &rules,
+            &path.to_string_lossy(),
+            content,
+        ])
+    }
+
+    /// The reports of the file if they are cached for this key.
+    pub fn get(&self, path: &Path, key: u64, source: &Arc<str>) -> Option<Vec<Report>> {
+        let entry = self
+            .file
+            .entries
+            .get(&entry_path(path))
+            .filter(|entry| entry.key == key)?;
+        Some(
+            entry
+                .reports
+                .iter()
+                .map(|report| report.to_report(source))
+                .collect(),
+        )
+    }
+
+    pub fn insert(&mut self, path: &Path, entry: Entry) {
+        self.file.entries.insert(entry_path(path), entry);
+        self.changed = true;
+    }
+
+    /// Writes the cache if anything changed, entries of files that don't exist anymore are dropped.
+    pub fn save(mut self) -> anyhow::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        self.file.entries.retain(|path, _| path.is_file());
+
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        // Keeps the cache out of version control without touching the project's `.gitignore`
+        let gitignore = self.dir.join(".gitignore");
+        if !gitignore.exists() {
+            files::write_atomic(&gitignore, "*\n")
+                .with_context(|| format!("failed to write {}", gitignore.display()))?;
+        }
+
+        let path = self.dir.join(CACHE_FILE);
+        let content = serde_json::to_string(&self.file).expect("the cache is serializable");
+        files::write_atomic(&path, &content)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// Entries are stored by absolute path, so it doesn't matter where the linter is run from.
+fn entry_path(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Removes the cache of the project at `root`, returns whether there was one.
+pub fn clean(root: &Path) -> anyhow::Result<bool> {
+    let dir = root.join(CACHE_DIR);
+    if !dir.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_dir_all(&dir).with_context(|| format!("failed to remove {}", dir.display()))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::fix::{Applicability, Edit};
+
+    #[test]
+    fn key_covers_the_inputs() {
+        let rules = RuleConfigs::default();
+        let key = Cache::key(&rules, Path::new("a.gd"), "pass\n");
+        assert_eq!(key, Cache::key(&rules, Path::new("a.gd"), "pass\n"));
+        assert_ne!(key, Cache::key(&rules, Path::new("b.gd"), "pass\n"));
+        assert_ne!(key, Cache::key(&rules, Path::new("a.gd"), "pass\n\n"));
+
+        let mut rules = RuleConfigs::default();
+        rules.entry("no-print".to_string()).or_default().enabled = Some(false);
+        assert_ne!(key, Cache::key(&rules, Path::new("a.gd"), "pass\n"));
+    }
+
+    #[test]
+    fn reports_survive_the_cache() {
+        let source = Arc::<str>::from("print(1)\n");
+        let report = miette::miette!(
+            severity = miette::Severity::Warning,
+            code = "no-print",
+            labels = vec![miette::LabeledSpan::at(0..5, "here")],
+            help = "don't",
+            "calling print is discouraged",
+        )
+        .with_source_code(Arc::clone(&source))
+        .with_fix(Fix::new(
+            Applicability::Unsafe,
+            vec![Edit::replace(0..5, "Log.info")],
+        ));
+
+        let json = serde_json::to_string(&CachedReport::new(&report)).unwrap();
+        let cached: CachedReport = serde_json::from_str(&json).unwrap();
+        let restored = cached.to_report(&source);
+
+        assert_eq!(format!("{restored:?}"), format!("{report:?}"));
+        assert_eq!(
+            fix::fix_of(&restored).unwrap().edits,
+            fix::fix_of(&report).unwrap().edits
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Restricts the diagnostics to lines that changed since a git revision, for `--changed-only`
+//! and `--diff-base`.
+//!
+//! Files are still linted as a whole, rules like `top-level-order` need the entire file, only
+//! the diagnostics outside of the changed lines are dropped.
+
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use miette::Report;
+
+use crate::line_index::LineIndex;
+
+/// What changed in a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Changed {
+    /// The file is new, every diagnostic is reported, also those about the whole file.
+    All,
+    /// The (one-based) lines that were added or modified.
+    Lines(Vec<RangeInclusive<usize>>),
+}
+
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// By canonical path.
+    files: HashMap<PathBuf, Changed>,
+}
+
+impl Changes {
+    /// Asks git what changed in the working tree since `base`, including uncommitted and
+    /// untracked files.
+    pub fn since(base: &str) -> anyhow::Result<Self> {
+        let root = git(Path::new("."), &["rev-parse", "--show-toplevel"])
+            .context("--changed-only needs a git repository")?;
+        let root = PathBuf::from(root.trim_end_matches(['\n', '\r']));
+        let root = root.canonicalize().unwrap_or(root);
+
+        let diff = git(
+            &root,
+            &[
+                "-c",
+                "core.quotePath=false",
+                "diff",
+                "--no-color",
+                "--no-ext-diff",
+                "--unified=0",
+                base,
+                "--",
+            ],
+        )
+        .with_context(|| format!("failed to find the changes since `{base}`"))?;
+        let mut changes = Self::default();
+        for (path, changed) in parse_diff(&diff) {
+            changes.files.insert(root.join(path), changed);
+        }
+
+        let untracked = git(&root, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+        for path in untracked.split('\0').filter(|path| !path.is_empty()) {
+            changes.files.insert(root.join(path), Changed::All);
+        }
+        Ok(changes)
+    }
+
+    fn get(&self, path: &Path) -> Option<&Changed> {
+        let path = path.canonicalize().ok()?;
+        self.files.get(&path)
+    }
+
+    /// Whether the file has changes, other files don't have to be linted.
+    pub fn touches(&self, path: &Path) -> bool {
+        self.get(path)
+            .is_some_and(|changed| *changed != Changed::Lines(Vec::new()))
+    }
+
+    /// Removes the reports that don't have a label on a changed line.
+    pub fn filter(&self, path: &Path, source: &str, reports: Vec<Report>) -> Vec<Report> {
+        let lines = match self.get(path) {
+            Some(Changed::All) => return reports,
+            Some(Changed::Lines(lines)) => lines,
+            None => return Vec::new(),
+        };
+        let index = LineIndex::new(source);
+        reports
+            .into_iter()
+            .filter(|report| {
+                report.labels().into_iter().flatten().any(|label| {
+                    // The end is exclusive, but empty labels still point at their line
+                    let start = index.line(label.offset()) + 1;
+                    let end = index.line(label.offset() + label.len().saturating_sub(1)) + 1;
+                    lines
+                        .iter()
+                        .any(|changed| start <= *changed.end() && *changed.start() <= end)
+                })
+            })
+            .collect()
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).context("git printed invalid UTF-8")
+}
+
+/// The changes per file (relative to the repository) of a `--unified=0` diff.
+fn parse_diff(diff: &str) -> Vec<(String, Changed)> {
+    let mut files: Vec<(String, Changed)> = Vec::new();
+    let mut new_file = false;
+    let mut in_deleted_file = false;
+    let mut previous = "";
+    for line in diff.lines() {
+        // Added lines can start with `++ ` too, but they never follow the `---` header
+        let header = previous.starts_with("--- ");
+        previous = line;
+        if line.starts_with("diff --git ") {
+            new_file = false;
+        } else if line.starts_with("new file mode ") {
+            new_file = true;
+        } else if header && let Some(path) = line.strip_prefix("+++ ") {
+            in_deleted_file = path == "/dev/null";
+            if !in_deleted_file {
+                let path = unquote(path);
+                let path = path.strip_prefix("b/").unwrap_or(&path);
+                let changed = match new_file {
+                    true => Changed::All,
+                    false => Changed::Lines(Vec::new()),
+                };
+                files.push((path.to_string(), changed));
+            }
+        } else if let Some(hunk) = line.strip_prefix("@@ ")
+            && !in_deleted_file
+            && let Some((_, Changed::Lines(lines))) = files.last_mut()
+            && let Some(added) = parse_hunk(hunk)
+        {
+            lines.push(added);
+        }
+    }
+    files
+}
+
+/// The added lines of a hunk header like `-3,2 +4,3 @@`, `None` for pure deletions.
+fn parse_hunk(hunk: &str) -> Option<RangeInclusive<usize>> {
+    let added = hunk.split(' ').find_map(|part| part.strip_prefix('+'))?;
+    let (start, count) = match added.split_once(',') {
+        Some((start, count)) => (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?),
+        None => (added.parse().ok()?, 1),
+    };
+    (count > 0).then(|| start..=start + count - 1)
+}
+
+/// Git quotes paths with special characters like C strings.
+fn unquote(path: &str) -> String {
+    let Some(quoted) = path
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+    else {
+        return path.to_string();
+    };
+    let mut bytes = Vec::new();
+    let mut chars = quoted.bytes().peekable();
+    while let Some(byte) = chars.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match chars.next() {
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(digit @ b'0'..=b'7') => {
+                // Octal escapes of UTF-8 bytes, e.g. `\303\244`
+                let mut value = u32::from(digit - b'0');
+                for _ in 0..2 {
+                    if let Some(digit @ b'0'..=b'7') = chars.peek().copied() {
+                        value = value * 8 + u32::from(digit - b'0');
+                        chars.next();
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            Some(other) => bytes.push(other),
+            None => {}
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/player.gd b/player.gd
+index 1111111..2222222 100644
+--- a/player.gd
++++ b/player.gd
+@@ -3,0 +4,2 @@ func _ready():
++\tprint(1)
++++ b/not_a_file.gd
+@@ -10 +12 @@ func _process(delta):
+-\tpass
++\tprint(3)
+@@ -20,2 +21,0 @@ func other():
+-\tpass
+-\tpass
+diff --git a/old.gd b/old.gd
+deleted file mode 100644
+--- a/old.gd
++++ /dev/null
+@@ -1 +0,0 @@
+-extends Node
+diff --git a/new.gd b/new.gd
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/new.gd
+@@ -0,0 +1 @@
++extends Node
+diff --git \"a/ui/m\\303\\244nu.gd\" \"b/ui/m\\303\\244nu.gd\"
+--- \"a/ui/m\\303\\244nu.gd\"
++++ \"b/ui/m\\303\\244nu.gd\"
+@@ -1 +1 @@
+-extends Node
++extends Control
+";
+
+    #[test]
+    fn parses_diffs() {
+        assert_eq!(
+            parse_diff(DIFF),
+            [
+                (
+                    "player.gd".to_string(),
+                    Changed::Lines(vec![4..=5, 12..=12])
+                ),
+                ("new.gd".to_string(), Changed::All),
+                ("ui/mänu.gd".to_string(), Changed::Lines(vec![1..=1])),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_reports_on_changed_lines() {
+        let source = Arc::<str>::from("a\nb\nc\nd\n");
+        let report = |offset: usize, len: usize| {
+            miette::miette!(
+                labels = vec![miette::LabeledSpan::at(offset..offset + len, "x")],
+                "x"
+            )
+            .with_source_code(Arc::clone(&source))
+        };
+        let path = std::env::current_dir().unwrap().join("Cargo.toml");
+        let changes = Changes {
+            files: HashMap::from([(path.canonicalize().unwrap(), Changed::Lines(vec![2..=2]))]),
+        };
+        let kept = changes.filter(
+            &path,
+            &source,
+            vec![
+                report(0, 1),
+                report(2, 0),
+                report(0, 3),
+                report(4, 1),
+                report(6, 1),
+            ],
+        );
+        let offsets = kept
+            .iter()
+            .map(|report| report.labels().unwrap().next().unwrap().offset())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [2, 0]);
+        assert!(
+            changes
+                .filter(Path::new("src/main.rs"), &source, vec![report(2, 1)])
+                .is_empty()
+        );
+    }
+}
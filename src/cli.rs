@@ -0,0 +1,187 @@
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  No issues found (or only warnings within --max-warnings)
+  1  Only warnings found
+  2  At least one error found (e.g. a syntax error)
+  3  The tool failed (e.g. a file couldn't be read)";
+
+const FORMAT_EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  All files are formatted (or were formatted in place)
+  1  Some files would be reformatted (with --check or --diff)
+  3  The tool failed (e.g. a file couldn't be read or has syntax errors)";
+
+/// A GDScript formatter and linter.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Use this configuration file instead of searching for `foli.toml`
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Check GDScript files for style and correctness issues
+    #[command(after_help = EXIT_CODES_HELP)]
+    Lint(LintArgs),
+    /// Format GDScript files
+    #[command(after_help = FORMAT_EXIT_CODES_HELP)]
+    Format(FormatArgs),
+    /// Manage the lint cache
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// Run a language server over stdio, for diagnostics, fixes and formatting in editors
+    Lsp,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// Remove the lint cache of the project
+    Clean,
+}
+
+#[derive(Debug, Args)]
+pub struct LintArgs {
+    #[command(flatten)]
+    pub files: FileArgs,
+
+    /// Exit successfully as long as there are at most this many warnings
+    #[arg(long, value_name = "N")]
+    pub max_warnings: Option<usize>,
+
+    /// Apply safe fixes to the files
+    #[arg(long, conflicts_with = "stdin")]
+    pub fix: bool,
+
+    /// Apply safe and unsafe fixes to the files, unsafe fixes may change the behavior of the code
+    #[arg(long, conflicts_with = "stdin")]
+    pub fix_unsafe: bool,
+
+    /// Lint this many files at once [default: number of CPUs]
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<NonZeroUsize>,
+
+    /// Don't read or write the cache of diagnostics of unchanged files
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// How to print the diagnostics
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Only report diagnostics that aren't in this baseline, the path is given as
+    /// `--baseline=PATH` [default path: foli-baseline.json]
+    #[arg(
+        long,
+        value_name = "PATH",
+        require_equals = true,
+        conflicts_with = "write_baseline"
+    )]
+    pub baseline: Option<Option<PathBuf>>,
+
+    /// Record the diagnostics of the linted files in a baseline instead of reporting them, the
+    /// path is given as `--write-baseline=PATH` [default path: foli-baseline.json]
+    #[arg(
+        long,
+        value_name = "PATH",
+        require_equals = true,
+        conflicts_with = "stdin"
+    )]
+    pub write_baseline: Option<Option<PathBuf>>,
+
+    /// Keep running and lint the files again whenever they change
+    #[arg(
+        long,
+        conflicts_with_all = ["fix", "fix_unsafe", "write_baseline", "changed_only", "diff_base", "stdin"]
+    )]
+    pub watch: bool,
+
+    /// Only report diagnostics on lines that changed since the last commit (or `--diff-base`),
+    /// according to git
+    #[arg(long, conflicts_with_all = ["write_baseline", "stdin"])]
+    pub changed_only: bool,
+
+    /// Only report diagnostics on lines that changed since this git revision, e.g. `main`
+    #[arg(long, value_name = "REV", conflicts_with_all = ["write_baseline", "stdin"])]
+    pub diff_base: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable diagnostics with the source code they point at
+    Text,
+    /// A single JSON document, see `docs/output.md` for the schema
+    Json,
+    /// One JSON object per diagnostic and line
+    #[value(name = "jsonl")]
+    JsonLines,
+    /// SARIF 2.1.0 for code scanning integrations
+    Sarif,
+    /// GitHub Actions workflow commands that annotate pull requests
+    Github,
+    /// GitLab Code Quality report
+    Gitlab,
+    /// Checkstyle XML
+    Checkstyle,
+    /// JUnit XML with a test suite per file
+    Junit,
+}
+
+#[derive(Debug, Args)]
+pub struct FormatArgs {
+    #[command(flatten)]
+    pub files: FileArgs,
+
+    /// Don't write the files, exit with 1 if any file would be reformatted
+    #[arg(long)]
+    pub check: bool,
+
+    /// Don't write the files, print a diff of the changes instead
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Split lines longer than this many columns (overrides `format.max-width` from `foli.toml`)
+    #[arg(long, value_name = "N")]
+    pub max_width: Option<usize>,
+
+    /// Format this many files at once [default: number of CPUs]
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<NonZeroUsize>,
+}
+
+/// Selects which files a command operates on.
+#[derive(Debug, Args)]
+pub struct FileArgs {
+    /// Files, directories or glob patterns to process
+    #[arg(default_value = ".")]
+    pub paths: Vec<String>,
+
+    /// Only process files matching this glob (can be repeated)
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip files matching this glob (can be repeated)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Don't skip files matched by the default excludes (`**/addons/**`)
+    #[arg(long)]
+    pub no_default_excludes: bool,
+
+    /// Read a single script from stdin instead of the files, for editor integrations
+    #[arg(long, conflicts_with_all = ["paths", "include", "exclude"])]
+    pub stdin: bool,
+
+    /// The path of the script read from stdin, the configuration is applied as if it were this
+    /// file
+    #[arg(long, value_name = "PATH", requires = "stdin")]
+    pub stdin_filename: Option<PathBuf>,
+}
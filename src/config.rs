@@ -0,0 +1,287 @@
+//! The `foli.toml` project configuration.
+//!
+//! The file is searched for in the current directory and all of its parents, or in the directory
+//! of `--stdin-filename` and its parents.
+//!
+//! ```toml
+//! [rules.no-print]
+//! severity = "error"
+//! functions = ["print", "prints"]
+//!
+//! [rules.line-length]
+//! max-length = 120
+//!
+//! # Later overrides win over earlier ones, globs are relative to this file.
+//! [[overrides]]
+//! files = ["test/**"]
+//! rules.no-print.enabled = false
+//!
+//! [format]
+//! max-width = 120
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use globset::GlobSet;
+use miette::Severity;
+use serde::{Deserialize, Serialize};
+
+use crate::{files, format::FormatOptions, lint};
+
+/// File names that are picked up as configuration, in order of preference.
+pub const CONFIG_FILE_NAMES: &[&str] = &["foli.toml", ".foli.toml"];
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeverityConfig {
+    Error,
+    Warning,
+    Advice,
+}
+
+impl From<SeverityConfig> for Severity {
+    fn from(severity: SeverityConfig) -> Self {
+        match severity {
+            SeverityConfig::Error => Severity::Error,
+            SeverityConfig::Warning => Severity::Warning,
+            SeverityConfig::Advice => Severity::Advice,
+        }
+    }
+}
+
+/// The settings of a single rule, see [`lint::RULES`] for the available rules.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RuleConfig {
+    pub enabled: Option<bool>,
+    pub severity: Option<SeverityConfig>,
+    /// Rule specific options, they're validated by the rule itself.
+    #[serde(flatten)]
+    pub options: toml::Table,
+}
+
+impl RuleConfig {
+    /// Applies the settings of `other` on top of `self`.
+    fn merge(&mut self, other: &RuleConfig) {
+        self.enabled = other.enabled.or(self.enabled);
+        self.severity = other.severity.or(self.severity);
+        self.options
+            .extend(other.options.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+/// Rule settings keyed by rule code.
+pub type RuleConfigs = BTreeMap<String, RuleConfig>;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    rules: RuleConfigs,
+    overrides: Vec<OverrideFile>,
+    format: FormatOptions,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OverrideFile {
+    files: Vec<String>,
+    #[serde(default)]
+    rules: RuleConfigs,
+}
+
+#[derive(Debug)]
+struct Override {
+    files: GlobSet,
+    rules: RuleConfigs,
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    /// The file this configuration was loaded from, if any.
+    pub path: Option<PathBuf>,
+    /// The directory override globs are relative to.
+    root: PathBuf,
+    rules: RuleConfigs,
+    overrides: Vec<Override>,
+    pub format: FormatOptions,
+}
+
+impl Config {
+    /// Searches `start` and all of its parents for a configuration file.
+    pub fn discover(start: &Path) -> Option<PathBuf> {
+        start.ancestors().find_map(|dir| {
+            CONFIG_FILE_NAMES
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.is_file())
+        })
+    }
+
+    /// Loads the configuration from `explicit`, or discovers it starting in the working directory.
+    ///
+    /// Falls back to the default configuration if there's no configuration file.
+    pub fn find(explicit: Option<&Path>) -> anyhow::Result<Self> {
+        let cwd = std::env::current_dir().context("getting the working directory")?;
+        Self::find_in(explicit, &cwd)
+    }
+
+    /// Like [`Config::find`], but discovers the configuration starting in the directory of
+    /// `path`, e.g. for a script that's read from stdin.
+    pub fn find_for(explicit: Option<&Path>, path: &Path) -> anyhow::Result<Self> {
+        let path =
+            std::path::absolute(path).with_context(|| format!("resolving {}", path.display()))?;
+        let dir = path.parent().unwrap_or(&path);
+        Self::find_in(explicit, dir)
+    }
+
+    fn find_in(explicit: Option<&Path>, dir: &Path) -> anyhow::Result<Self> {
+        match explicit
+            .map(Path::to_path_buf)
+            .or_else(|| Self::discover(dir))
+        {
+            Some(path) => Self::load(&path),
+            None => Ok(Self {
+                root: dir.to_path_buf(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let path =
+            std::path::absolute(path).with_context(|| format!("resolving {}", path.display()))?;
+        let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        let config = Self::parse(&content, root)
+            .with_context(|| format!("invalid configuration in {}", path.display()))?;
+        Ok(Self {
+            path: Some(path),
+            ..config
+        })
+    }
+
+    /// Parses a configuration, override globs are relative to `root`.
+    fn parse(content: &str, root: PathBuf) -> anyhow::Result<Self> {
+        let file: ConfigFile = toml::from_str(content)?;
+
+        let all_rules = std::iter::once(&file.rules)
+            .chain(file.overrides.iter().map(|o| &o.rules))
+            .flatten();
+        for (code, config) in all_rules {
+            lint::validate_rule_config(code, config)?;
+        }
+
+        let overrides = file
+            .overrides
+            .into_iter()
+            .map(|o| {
+                Ok(Override {
+                    files: files::build_glob_set(o.files.iter().map(String::as_str))?,
+                    rules: o.rules,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            path: None,
+            root,
+            rules: file.rules,
+            overrides,
+            format: file.format,
+        })
+    }
+
+    /// The directory of the configuration file, or the working directory without one.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the effective rule settings for the file at `path`.
+    pub fn rules_for(&self, path: &Path) -> RuleConfigs {
+        let mut rules = self.rules.clone();
+
+        let relative = std::path::absolute(path)
+            .ok()
+            .and_then(|path| path.strip_prefix(&self.root).ok().map(Path::to_path_buf));
+        let Some(relative) = relative else {
+            return rules;
+        };
+
+        for o in &self.overrides {
+            if !files::matches(&o.files, &relative) {
+                continue;
+            }
+            for (code, config) in &o.rules {
+                rules.entry(code.clone()).or_default().merge(config);
+            }
+        }
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [rules.no-print]
+        severity = "error"
+        functions = ["print", "prints"]
+
+        [[overrides]]
+        files = ["test/**"]
+        rules.no-print.enabled = false
+
+        [[overrides]]
+        files = ["test/unit/**"]
+        rules.no-print = { enabled = true, functions = ["printt"] }
+    "#;
+
+    #[test]
+    fn overrides_are_merged_in_order() {
+        let root = std::path::absolute("project").unwrap();
+        let config = Config::parse(CONFIG, root.clone()).unwrap();
+
+        let rules = config.rules_for(&root.join("player.gd"));
+        let no_print = &rules["no-print"];
+        assert_eq!(no_print.enabled, None);
+        assert!(matches!(no_print.severity, Some(SeverityConfig::Error)));
+
+        let rules = config.rules_for(&root.join("test/player_test.gd"));
+        assert_eq!(rules["no-print"].enabled, Some(false));
+
+        let rules = config.rules_for(&root.join("test/unit/player_test.gd"));
+        let no_print = &rules["no-print"];
+        assert_eq!(no_print.enabled, Some(true));
+        assert!(matches!(no_print.severity, Some(SeverityConfig::Error)));
+        assert_eq!(no_print.options["functions"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn unknown_rules_and_options_are_rejected() {
+        let root = PathBuf::from("/");
+        assert!(Config::parse("[rules.no-such-rule]", root.clone()).is_err());
+        assert!(Config::parse("[rules.no-print]\nfunctionz = []", root).is_err());
+    }
+
+    #[test]
+    fn found_from_the_stdin_filename() {
+        let dir = std::env::temp_dir().join(format!("foli-config-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("test")).unwrap();
+        std::fs::write(dir.join("foli.toml"), CONFIG).unwrap();
+
+        // The file doesn't have to exist, only its directory is searched
+        let path = dir.join("test/player_test.gd");
+        let config = Config::find_for(None, &path).unwrap();
+        assert_eq!(config.root(), dir);
+        assert_eq!(config.rules_for(&path)["no-print"].enabled, Some(false));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
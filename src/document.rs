@@ -0,0 +1,158 @@
+//! Scripts that are kept in memory together with their syntax tree, so tree-sitter only has to
+//! parse the changed parts again, for `lint --watch` and the language server.
+
+use std::{ops::Range, sync::Arc};
+
+use anyhow::Context;
+use tree_sitter::{InputEdit, Point, Tree};
+
+use crate::line_index::LineIndex;
+
+pub struct Document {
+    pub source: Arc<str>,
+    pub tree: Tree,
+}
+
+impl Document {
+    pub fn parse(parser: &mut tree_sitter::Parser, source: Arc<str>) -> anyhow::Result<Self> {
+        let tree = parser
+            .parse(source.as_bytes(), None)
+            .context("failed to parse file")?;
+        Ok(Self { source, tree })
+    }
+
+    /// Replaces the source and parses it again, reusing the parts of the old tree that didn't
+    /// change.
+    ///
+    /// Returns whether the source changed.
+    pub fn update(
+        &mut self,
+        parser: &mut tree_sitter::Parser,
+        source: Arc<str>,
+    ) -> anyhow::Result<bool> {
+        let Some((old_range, new_range)) = changed_range(&self.source, &source) else {
+            return Ok(false);
+        };
+        self.edit(old_range, &source[new_range]);
+        self.reparse(parser)?;
+        Ok(true)
+    }
+
+    /// Replaces a byte range of the source, the tree is updated by [`Document::reparse`] after
+    /// the last edit.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) {
+        let source = [&self.source[..range.start], text, &self.source[range.end..]].concat();
+        let new_range = range.start..range.start + text.len();
+        self.tree
+            .edit(&input_edit(&self.source, &source, (range, new_range)));
+        self.source = source.into();
+    }
+
+    /// Parses the edited source, reusing the parts of the old tree that didn't change.
+    pub fn reparse(&mut self, parser: &mut tree_sitter::Parser) -> anyhow::Result<()> {
+        self.tree = parser
+            .parse(self.source.as_bytes(), Some(&self.tree))
+            .context("failed to parse file")?;
+        Ok(())
+    }
+}
+
+/// The changed part of the source, in the old source and the new one. `None` if the sources
+/// are the same.
+pub fn changed_range(old: &str, new: &str) -> Option<(Range<usize>, Range<usize>)> {
+    if old == new {
+        return None;
+    }
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    Some((prefix..old.len() - suffix, prefix..new.len() - suffix))
+}
+
+/// Describes the replacement of `old[changed.0]` with `new[changed.1]` for [`Tree::edit`].
+fn input_edit(
+    old: &str,
+    new: &str,
+    (old_range, new_range): (Range<usize>, Range<usize>),
+) -> InputEdit {
+    let (old_lines, new_lines) = (LineIndex::new(old), LineIndex::new(new));
+    let point = |lines: &LineIndex, offset: usize| {
+        let row = lines.line(offset);
+        Point::new(row, offset - lines.line_start(row))
+    };
+    InputEdit {
+        start_byte: old_range.start,
+        old_end_byte: old_range.end,
+        new_end_byte: new_range.end,
+        start_position: point(&old_lines, old_range.start),
+        old_end_position: point(&old_lines, old_range.end),
+        new_end_position: point(&new_lines, new_range.end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_ranges() {
+        assert_eq!(changed_range("abc", "abc"), None);
+        assert_eq!(changed_range("abc", "abxc"), Some((2..2, 2..3)));
+        assert_eq!(changed_range("aaa", "aa"), Some((2..3, 2..2)));
+        // Only whole characters are replaced
+        assert_eq!(changed_range("ä", "ö"), Some((0..2, 0..2)));
+    }
+
+    #[test]
+    fn reparses_incrementally() {
+        let mut parser = crate::new_parser().unwrap();
+        let mut document = Document::parse(
+            &mut parser,
+            "extends Node\n\nfunc f():\n\tpass\n\n\nfunc g():\n\tpass\n".into(),
+        )
+        .unwrap();
+        let edits = [
+            "extends Node\n\nfunc f():\n\tprint(1)\n\n\nfunc g():\n\tpass\n",
+            "extends Node\n\nvar ä := 1\n\nfunc f():\n\tprint(1)\n\n\nfunc g():\n\tpass\n",
+            "extends Node\n\nvar ä := 1\n\nfunc f():\n\tprint(1)\n\tif true:\n\t\tpass\n",
+            "extends Node\n\nvar ö := 1\n\nfunc f(:\n",
+        ];
+        for source in edits {
+            assert!(document.update(&mut parser, source.into()).unwrap());
+            let fresh = parser.parse(source, None).unwrap();
+            assert_eq!(
+                document.tree.root_node().to_sexp(),
+                fresh.root_node().to_sexp()
+            );
+        }
+        assert!(!document.update(&mut parser, edits[3].into()).unwrap());
+
+        // Several edits before parsing again, like the changes of an editor
+        document.edit(26..26, "\n");
+        document.edit(0..8, "");
+        document.reparse(&mut parser).unwrap();
+        let source = "Node\n\nvar ö := 1\n\n\nfunc f(:\n";
+        assert_eq!(&*document.source, source);
+        let fresh = parser.parse(source, None).unwrap();
+        assert_eq!(
+            document.tree.root_node().to_sexp(),
+            fresh.root_node().to_sexp()
+        );
+    }
+}
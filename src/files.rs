@@ -0,0 +1,337 @@
+//! Resolves the paths given on the command line to a list of GDScript files.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::cli::FileArgs;
+
+/// Globs that are excluded unless `--no-default-excludes` is given.
+pub const DEFAULT_EXCLUDES: &[&str] = &["**/addons/**"];
+
+/// The path a script read from stdin is reported as, unless `--stdin-filename` is given.
+pub const STDIN_PATH: &str = "<stdin>";
+
+/// Decides whether a discovered file should be processed.
+///
+/// Patterns containing a `/` are matched against the whole path, all other
+/// patterns only against the file name (similar to `.gitignore`).
+pub struct FileFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl FileFilter {
+    pub fn new(args: &FileArgs) -> anyhow::Result<Self> {
+        let include = match args.include.is_empty() {
+            true => None,
+            false => Some(build_glob_set(args.include.iter().map(String::as_str))?),
+        };
+
+        let defaults = match args.no_default_excludes {
+            true => &[][..],
+            false => DEFAULT_EXCLUDES,
+        };
+        let exclude = build_glob_set(
+            defaults
+                .iter()
+                .copied()
+                .chain(args.exclude.iter().map(String::as_str)),
+        )?;
+
+        Ok(Self { include, exclude })
+    }
+
+    pub fn is_included(&self, path: &Path) -> bool {
+        let matches = |set: &GlobSet| matches(set, path);
+        self.include.as_ref().is_none_or(matches) && !matches(&self.exclude)
+    }
+}
+
+/// Matches patterns containing a `/` against the whole path, all others against the file name.
+pub fn matches(set: &GlobSet, path: &Path) -> bool {
+    set.is_match(path) || path.file_name().is_some_and(|name| set.is_match(name))
+}
+
+pub fn build_glob_set<'a>(patterns: impl IntoIterator<Item = &'a str>) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("invalid glob pattern {pattern:?}"))?,
+        );
+    }
+    builder.build().context("building glob set")
+}
+
+/// Strips a leading `./` so paths look the same no matter how they were given.
+fn normalize(path: &Path) -> PathBuf {
+    path.strip_prefix(".").unwrap_or(path).to_path_buf()
+}
+
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '{'])
+}
+
+/// Returns the longest leading directory of a glob pattern without any meta characters.
+pub fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| !is_glob(&component.as_os_str().to_string_lossy()))
+        .collect::<PathBuf>()
+}
+
+fn walk(
+    root: &Path,
+    glob: Option<&GlobSet>,
+    filter: &FileFilter,
+    files: &mut Vec<PathBuf>,
+) -> usize {
+    let mut found = 0;
+    for entry in walkdir::WalkDir::new(root) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::warn!("skipping unreadable path: {err}");
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() || entry.path().extension().is_none_or(|ext| ext != "gd") {
+            continue;
+        }
+
+        let path = normalize(entry.path());
+        if glob.is_some_and(|glob| !glob.is_match(&path)) || !filter.is_included(&path) {
+            continue;
+        }
+        files.push(path);
+        found += 1;
+    }
+    found
+}
+
+/// Expands files, directories and glob patterns into a sorted list of files.
+///
+/// Files given explicitly are always returned, even if the include/exclude
+/// patterns would have filtered them out during a directory walk.
+pub fn collect_files(args: &FileArgs) -> anyhow::Result<Vec<PathBuf>> {
+    let filter = FileFilter::new(args)?;
+    let mut files = Vec::new();
+
+    for arg in &args.paths {
+        let path = Path::new(arg);
+        if path.is_file() {
+            files.push(normalize(path));
+        } else if path.is_dir() {
+            walk(path, None, &filter, &mut files);
+        } else if is_glob(arg) {
+            // Path arguments are matched against the whole path
+            let glob = build_glob_set([&*normalize(path).to_string_lossy()])?;
+            let base = glob_base(arg);
+            let base = match base.as_os_str().is_empty() {
+                true => Path::new("."),
+                false => base.as_path(),
+            };
+            if walk(base, Some(&glob), &filter, &mut files) == 0 {
+                tracing::warn!("pattern {arg:?} didn't match any files");
+            }
+        } else {
+            anyhow::bail!("path {arg:?} does not exist");
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Replaces the content of a file by writing a temporary file next to it and renaming it.
+///
+/// Editors and Godot's file watcher never see a partially written file this way.
+pub fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{name}.{}.tmp", std::process::id()));
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&temp)?;
+        std::io::Write::write_all(&mut file, content.as_bytes())?;
+        file.sync_all()?;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        std::fs::rename(&temp, path)
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        files: FileArgs,
+    }
+
+    fn args(args: &[&str]) -> FileArgs {
+        Cli::parse_from(std::iter::once("foli").chain(args.iter().copied())).files
+    }
+
+    /// The collected files relative to `dir`.
+    fn collect(dir: &Path, args: &FileArgs) -> Vec<String> {
+        collect_files(args)
+            .unwrap()
+            .iter()
+            .map(|path| {
+                let path = path.strip_prefix(dir).unwrap_or(path);
+                path.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn collects_files() {
+        let dir = std::env::temp_dir().join(format!("foli-collect-{}", std::process::id()));
+        for path in [
+            "main.gd",
+            "notes.txt",
+            "player/player.gd",
+            "player/_test_player.gd",
+            "player/player.tscn",
+            "addons/plugin/plugin.gd",
+            "ui/addons/menu.gd",
+        ] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let root = dir.to_str().unwrap();
+
+        // `addons` directories are excluded by default, at any depth
+        assert_eq!(
+            collect(&dir, &args(&[root])),
+            ["main.gd", "player/_test_player.gd", "player/player.gd"]
+        );
+        assert_eq!(
+            collect(&dir, &args(&[root, "--no-default-excludes"])),
+            [
+                "addons/plugin/plugin.gd",
+                "main.gd",
+                "player/_test_player.gd",
+                "player/player.gd",
+                "ui/addons/menu.gd",
+            ]
+        );
+
+        // Patterns without a `/` match the file name, others the whole path
+        assert_eq!(
+            collect(&dir, &args(&[root, "--exclude", "_*.gd"])),
+            ["main.gd", "player/player.gd"]
+        );
+        assert_eq!(
+            collect(&dir, &args(&[root, "--include", "**/player/*"])),
+            ["player/_test_player.gd", "player/player.gd"]
+        );
+        // Excludes replace the default ones only with `--no-default-excludes`
+        assert_eq!(
+            collect(
+                &dir,
+                &args(&[root, "--no-default-excludes", "--exclude", "**/player/**"])
+            ),
+            ["addons/plugin/plugin.gd", "main.gd", "ui/addons/menu.gd"]
+        );
+        // Negated character classes
+        assert_eq!(
+            collect(&dir, &args(&[root, "--include", "[!_]*.gd"])),
+            ["main.gd", "player/player.gd"]
+        );
+
+        // Glob arguments match the whole path and are filtered too
+        let glob = format!("{root}/**/player*.gd");
+        assert_eq!(
+            collect(&dir, &args(&[&glob, "--no-default-excludes"])),
+            ["player/player.gd"]
+        );
+        let glob = format!("{root}/**/*.gd");
+        assert_eq!(
+            collect(&dir, &args(&[&glob, "--exclude", "main.gd"])),
+            ["player/_test_player.gd", "player/player.gd"]
+        );
+
+        // Files given explicitly are kept, and collected once
+        let plugin = dir.join("addons/plugin/plugin.gd");
+        let plugin = plugin.to_str().unwrap();
+        assert_eq!(
+            collect(&dir, &args(&[plugin, root, plugin])),
+            [
+                "addons/plugin/plugin.gd",
+                "main.gd",
+                "player/_test_player.gd",
+                "player/player.gd",
+            ]
+        );
+
+        assert!(collect_files(&args(&[&format!("{root}/missing.gd")])).is_err());
+        assert!(FileFilter::new(&args(&["--exclude", "[a"])).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn filters() {
+        let filter =
+            FileFilter::new(&args(&["--include", "src/**", "--exclude", "*_test.gd"])).unwrap();
+        assert!(filter.is_included(Path::new("src/a.gd")));
+        assert!(filter.is_included(Path::new("src/b/a.gd")));
+        assert!(!filter.is_included(Path::new("a.gd")));
+        assert!(!filter.is_included(Path::new("src/a_test.gd")));
+        assert!(!filter.is_included(Path::new("src/addons/a.gd")));
+
+        // `*` doesn't cross directories
+        let set = build_glob_set(["src/*.gd"]).unwrap();
+        assert!(matches(&set, Path::new("src/a.gd")));
+        assert!(!matches(&set, Path::new("src/b/a.gd")));
+        assert_eq!(glob_base("src/**/*.gd"), Path::new("src"));
+        assert_eq!(glob_base("*.gd"), Path::new(""));
+    }
+
+    #[test]
+    fn writes_atomically() {
+        let dir = std::env::temp_dir().join(format!("foli-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.gd");
+
+        write_atomic(&path, "var a = 1\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "var a = 1\n");
+        write_atomic(&path, "var b = 2\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "var b = 2\n");
+        // The temporary file is gone
+        let names = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a.gd"]);
+
+        // A failed write leaves nothing behind, a file can't replace a directory
+        std::fs::create_dir(dir.join("b.gd")).unwrap();
+        assert!(write_atomic(&dir.join("b.gd"), "").is_err());
+        let mut names = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["a.gd", "b.gd"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
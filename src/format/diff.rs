@@ -0,0 +1,67 @@
+use std::{
+    fmt::Write,
+    io::{IsTerminal, stdout},
+    path::Path,
+};
+
+use owo_colors::{OwoColorize, Style};
+use similar::{ChangeTag, TextDiff};
+
+/// Whether diffs printed to stdout are colored: only on a terminal and without `NO_COLOR`.
+pub fn use_color() -> bool {
+    stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Renders a unified diff between the original and the formatted source, colored if `color` is
+/// set.
+pub fn unified_diff(path: &Path, original: &str, formatted: &str, color: bool) -> String {
+    let diff = TextDiff::from_lines(original, formatted);
+    let paint = |text: String, style: Style| match color {
+        true => text.style(style).to_string(),
+        false => text,
+    };
+
+    let mut out = String::new();
+    let path = path.display();
+    writeln!(out, "{}", paint(format!("--- {path}"), Style::new().bold())).unwrap();
+    writeln!(out, "{}", paint(format!("+++ {path}"), Style::new().bold())).unwrap();
+
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        writeln!(
+            out,
+            "{}",
+            paint(hunk.header().to_string(), Style::new().cyan())
+        )
+        .unwrap();
+        for change in hunk.iter_changes() {
+            let line = change.value().trim_end_matches(['\r', '\n']);
+            let line = match change.tag() {
+                ChangeTag::Delete => paint(format!("-{line}"), Style::new().red()),
+                ChangeTag::Insert => paint(format!("+{line}"), Style::new().green()),
+                ChangeTag::Equal => format!(" {line}"),
+            };
+            writeln!(out, "{line}").unwrap();
+            if change.missing_newline() {
+                writeln!(out, "\\ No newline at end of file").unwrap();
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        let path = Path::new("a.gd");
+        let plain = unified_diff(path, "var a=1\n", "var a = 1\n", false);
+        assert_eq!(
+            plain,
+            "--- a.gd\n+++ a.gd\n@@ -1 +1 @@\n-var a=1\n+var a = 1\n"
+        );
+        let colored = unified_diff(path, "var a=1\n", "var a = 1\n", true);
+        assert!(colored.contains("\u{1b}[31m-var a=1"));
+    }
+}
@@ -0,0 +1,170 @@
+//! A small pretty printing document model, in the spirit of Wadler's "prettier printer".
+//!
+//! A [`Doc::Group`] is printed on a single line if it fits into the remaining width,
+//! otherwise its line breaks are taken, starting with the outermost group.
+
+#[derive(Debug, Clone)]
+pub enum Doc {
+    Text(String),
+    /// A space, or a line break if the enclosing group is split.
+    Line,
+    /// Nothing, or a line break if the enclosing group is split.
+    SoftLine,
+    /// Only printed if the enclosing group is split, e.g. a trailing comma.
+    IfBreak(&'static str),
+    Indent(Box<Doc>),
+    Group {
+        doc: Box<Doc>,
+        /// Split no matter the width, e.g. because of a magic trailing comma.
+        broken: bool,
+    },
+    Concat(Vec<Doc>),
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// A group is split if any group inside of it has to be split, this keeps the formatting
+    /// stable as split lists get a (magic) trailing comma.
+    pub fn group(docs: Vec<Doc>, broken: bool) -> Self {
+        let broken = broken || docs.iter().any(Doc::is_broken);
+        Self::Group {
+            doc: Box::new(Self::Concat(docs)),
+            broken,
+        }
+    }
+
+    pub fn indent(docs: Vec<Doc>) -> Self {
+        Self::Indent(Box::new(Self::Concat(docs)))
+    }
+
+    fn is_broken(&self) -> bool {
+        match self {
+            Self::Group { broken, .. } => *broken,
+            Self::Indent(doc) => doc.is_broken(),
+            Self::Concat(docs) => docs.iter().any(Doc::is_broken),
+            Self::Text(_) | Self::Line | Self::SoftLine | Self::IfBreak(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+type Command<'d> = (usize, Mode, &'d Doc);
+
+/// Whether the rest of the line fits into `width` columns, with `next` printed flat.
+fn fits(next: Command, rest: &[Command], mut width: isize) -> bool {
+    let mut stack = vec![next];
+    let mut rest = rest.iter().rev();
+
+    while width >= 0 {
+        let Some((indent, mode, doc)) = stack.pop().or_else(|| rest.next().copied()) else {
+            return true;
+        };
+        match doc {
+            Doc::Text(text) => width -= text.chars().count() as isize,
+            Doc::Line | Doc::SoftLine if mode == Mode::Break => return true,
+            Doc::Line => width -= 1,
+            Doc::SoftLine => {}
+            Doc::IfBreak(text) => {
+                if mode == Mode::Break {
+                    width -= text.chars().count() as isize;
+                }
+            }
+            Doc::Indent(doc) => stack.push((indent + 1, mode, doc)),
+            Doc::Group { doc, broken } => {
+                let mode = match broken {
+                    true => Mode::Break,
+                    false => mode,
+                };
+                stack.push((indent, mode, doc));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+    }
+    false
+}
+
+/// Prints the document at the given indentation level.
+///
+/// The first line is returned without indentation, the following lines are indented with tabs.
+pub fn render(doc: &Doc, level: usize, max_width: usize, tab_width: usize) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    let mut column = level * tab_width;
+    let mut stack: Vec<Command> = vec![(level, Mode::Break, doc)];
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                lines.last_mut().expect("at least one line").push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Line | Doc::SoftLine if mode == Mode::Break => {
+                lines.push("\t".repeat(indent));
+                column = indent * tab_width;
+            }
+            Doc::Line => {
+                lines.last_mut().expect("at least one line").push(' ');
+                column += 1;
+            }
+            Doc::SoftLine => {}
+            Doc::IfBreak(text) => {
+                if mode == Mode::Break {
+                    lines.last_mut().expect("at least one line").push_str(text);
+                    column += text.chars().count();
+                }
+            }
+            Doc::Indent(doc) => stack.push((indent + 1, mode, doc)),
+            Doc::Group { doc, broken } => {
+                let flat = (indent, Mode::Flat, &**doc);
+                let remaining = max_width as isize - column as isize;
+                let mode = match mode {
+                    Mode::Flat if !broken => Mode::Flat,
+                    _ if !broken && fits(flat, &stack, remaining) => Mode::Flat,
+                    _ => Mode::Break,
+                };
+                stack.push((indent, mode, doc));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[&str], broken: bool) -> Doc {
+        let mut docs = vec![Doc::text("f(")];
+        let mut inner = vec![Doc::SoftLine];
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 {
+                inner.extend([Doc::text(","), Doc::Line]);
+            }
+            inner.push(Doc::text(*item));
+        }
+        inner.push(Doc::IfBreak(","));
+        docs.extend([Doc::indent(inner), Doc::SoftLine, Doc::text(")")]);
+        Doc::group(docs, broken)
+    }
+
+    #[test]
+    fn groups_break_when_too_wide() {
+        let doc = list(&["aaa", "bbb"], false);
+        assert_eq!(render(&doc, 0, 20, 4), vec!["f(aaa, bbb)"]);
+        assert_eq!(render(&doc, 0, 10, 4), vec!["f(", "\taaa,", "\tbbb,", ")"]);
+        // The indentation counts towards the width
+        assert_eq!(render(&doc, 3, 20, 4).len(), 4);
+        assert_eq!(
+            render(&list(&["a"], true), 1, 80, 4),
+            vec!["f(", "\t\ta,", "\t)"]
+        );
+    }
+}
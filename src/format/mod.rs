@@ -0,0 +1,546 @@
+//! A formatter for GDScript, following the [style guide](https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/gdscript_styleguide.html).
+//!
+//! - indentation with tabs
+//! - single spaces around operators and after commas
+//! - two blank lines around functions and classes, at most one blank line elsewhere
+//! - comments and doc comments are kept as written
+//! - lines longer than [`FormatOptions::max_width`] are split inside of brackets, one item per line
+//! - lists with a trailing comma ("magic trailing comma") or comments between their items are
+//!   always split, the comments stay with their items
+//!
+//! Code with other comments (e.g. inside of an expression), multiline strings or multiline
+//! lambdas inside of it is re-indented, but otherwise kept as is.
+
+use std::ops::Range;
+
+use serde::Deserialize;
+use similar::{DiffTag, TextDiff};
+use tree_sitter::{Node, Parser};
+
+use crate::{line_index::LineIndex, node_ext::collect_comments};
+
+mod diff;
+mod layout;
+mod printer;
+mod tree;
+
+pub use diff::{unified_diff, use_color};
+
+/// The `[format]` section of `foli.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FormatOptions {
+    /// Lines longer than this many columns are split where possible.
+    pub max_width: usize,
+    /// How many columns a tab character advances to.
+    pub tab_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            max_width: 100,
+            tab_width: 4,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FormatError {
+    #[error("failed to parse the source")]
+    Parse,
+    #[error("the source has syntax errors")]
+    Syntax,
+    #[error("formatting would change the meaning of the code, this is a bug in the formatter")]
+    ChangedSemantics,
+}
+
+/// Formats a whole script.
+pub fn format_code(
+    parser: &mut Parser,
+    source: &str,
+    options: &FormatOptions,
+) -> Result<String, FormatError> {
+    let tree = parser.parse(source, None).ok_or(FormatError::Parse)?;
+    let root = tree.root_node();
+    if root.has_error() {
+        return Err(FormatError::Syntax);
+    }
+    if source.trim().is_empty() {
+        return Ok(String::new());
+    }
+
+    let lines = LineIndex::new(source);
+    let indent_width = detect_indent_width(source);
+
+    let mut builder = tree::Builder::new(source, &lines, indent_width);
+    let block = builder.block(root, root, None);
+    let comments = builder.comments(root);
+
+    let mut printer = printer::Printer::new(source, &lines, indent_width, options, comments, root);
+    printer.block(&block, 0);
+    let formatted = printer.finish();
+
+    // Make sure nothing but whitespace changed
+    let formatted_tree = parser.parse(&formatted, None).ok_or(FormatError::Parse)?;
+    let formatted_root = formatted_tree.root_node();
+    if formatted_root.has_error()
+        || fingerprint(root, source) != fingerprint(formatted_root, &formatted)
+        || comment_texts(root, source) != comment_texts(formatted_root, &formatted)
+    {
+        return Err(FormatError::ChangedSemantics);
+    }
+
+    // The output is built with `\n`, keep Windows line endings if the script uses them
+    match source.contains("\r\n") {
+        true => Ok(formatted.replace('\n', "\r\n")),
+        false => Ok(formatted),
+    }
+}
+
+/// Formats the lines of a script that overlap the byte range, the other lines are kept as they are.
+///
+/// The whole script is formatted, a change is only taken over if it replaces lines in the range.
+pub fn format_range(
+    parser: &mut Parser,
+    source: &str,
+    range: Range<usize>,
+    options: &FormatOptions,
+) -> Result<String, FormatError> {
+    let formatted = format_code(parser, source, options)?;
+
+    let lines = LineIndex::new(source);
+    let first = lines.line(range.start);
+    let last = lines.line(range.end.max(range.start + 1) - 1);
+
+    let diff = TextDiff::from_lines(source, &formatted);
+    let (old_lines, new_lines) = (diff.old_slices(), diff.new_slices());
+    let mut result = String::with_capacity(source.len());
+    for op in diff.ops() {
+        let (old, new) = (op.old_range(), op.new_range());
+        // Adjacent changed lines end up in the same op, split them again if they still match up
+        let changes = match op.tag() == DiffTag::Replace && old.len() == new.len() {
+            true => old
+                .zip(new)
+                .map(|(old, new)| (old..old + 1, new..new + 1))
+                .collect(),
+            false => vec![(old, new)],
+        };
+        for (old, new) in changes {
+            // Lines can be inserted before the first line of the range
+            let in_range = old.start <= last && first < old.end.max(old.start + 1);
+            let taken = match op.tag() != DiffTag::Equal && in_range {
+                true => &new_lines[new],
+                false => &old_lines[old],
+            };
+            result.extend(taken.iter().copied());
+        }
+    }
+    Ok(result)
+}
+
+/// The smallest indentation with spaces, for scripts that aren't indented with tabs.
+fn detect_indent_width(source: &str) -> usize {
+    source
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(' ').len())
+        .filter(|&spaces| spaces > 0)
+        .min()
+        .unwrap_or(4)
+}
+
+/// The structure of the tree with the text of its tokens, ignoring comments and optional punctuation.
+///
+/// Parentheses are ignored as well, the formatter adds them around expressions that are split.
+fn fingerprint(root: Node, source: &str) -> Vec<String> {
+    fn visit(node: Node, source: &[u8], out: &mut Vec<String>) {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.kind() == "comment" || child.kind() == ";" || printer::is_trailing_comma(child)
+            {
+                continue;
+            }
+            if child.kind() == "parenthesized_expression" {
+                visit(child, source, out);
+            } else if matches!(child.kind(), "(" | ")") && node.kind() == "parenthesized_expression"
+            {
+                continue;
+            } else if tree::is_atomic(child, source) {
+                let text = String::from_utf8_lossy(&source[child.byte_range()]);
+                out.push(format!("{}:{}", child.kind(), text.replace("\r\n", "\n")));
+            } else {
+                out.push(format!("({}", child.kind()));
+                visit(child, source, out);
+                out.push(")".to_string());
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    visit(root, source.as_bytes(), &mut out);
+    out
+}
+
+fn comment_texts<'a>(root: Node, source: &'a str) -> Vec<&'a str> {
+    let mut comments = Vec::new();
+    collect_comments(root, &mut comments);
+    comments
+        .into_iter()
+        .map(|comment| source[comment.byte_range()].trim_end())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+        let options = FormatOptions {
+            max_width: 40,
+            ..Default::default()
+        };
+        let formatted = format_code(&mut parser, source, &options).unwrap();
+        let again = format_code(&mut parser, &formatted, &options).unwrap();
+        assert_eq!(formatted, again, "formatting isn't idempotent");
+        formatted
+    }
+
+    #[test]
+    fn ranges() {
+        let source = "var a=1\nvar b=2\nvar c=3\n";
+        let mut parser = crate::new_parser().unwrap();
+        let options = FormatOptions::default();
+        let mut format = |range| format_range(&mut parser, source, range, &options).unwrap();
+        assert_eq!(format(8..15), "var a=1\nvar b = 2\nvar c=3\n");
+        assert_eq!(format(3..3), "var a = 1\nvar b=2\nvar c=3\n");
+        assert_eq!(format(3..20), "var a = 1\nvar b = 2\nvar c = 3\n");
+    }
+
+    #[test]
+    fn spacing() {
+        assert_eq!(
+            format("var a=foo( 1,2 )+-b [0]\n"),
+            "var a = foo(1, 2) + -b[0]\n"
+        );
+        assert_eq!(
+            format("func f(a:int,b=2)->void:\n  return not(a or b)\n"),
+            "func f(a: int, b = 2) -> void:\n\treturn not (a or b)\n"
+        );
+        assert_eq!(
+            format("var d = {\"a\" : 1, b = [1, 2 ]}\n"),
+            "var d = {\"a\": 1, b = [1, 2]}\n"
+        );
+        assert_eq!(
+            format("var n = [&\"a\", &'a', ^\"a/b\", a&b]\n"),
+            "var n = [&\"a\", &'a', ^\"a/b\", a & b]\n"
+        );
+    }
+
+    #[test]
+    fn blank_lines() {
+        let source = "\
+@tool
+extends Node
+## The class docs.
+signal a
+var b = 1
+
+
+
+var c = 2
+func f():
+	pass
+func g():
+
+	pass
+class Inner:
+	var x
+	func h():
+		pass
+";
+        let expected = "\
+@tool
+extends Node
+## The class docs.
+
+signal a
+var b = 1
+
+var c = 2
+
+
+func f():
+	pass
+
+
+func g():
+	pass
+
+
+class Inner:
+	var x
+
+
+	func h():
+		pass
+";
+        assert_eq!(format(source), expected);
+
+        // Annotations on their own lines stay with the definition they apply to
+        let source = "\
+var a
+@rpc(\"any_peer\")
+func f():
+	pass
+class Inner:
+	var x
+	@warning_ignore(\"unused\")
+	# Why it's unused
+	func g():
+		pass
+";
+        let expected = "\
+var a
+
+
+@rpc(\"any_peer\")
+func f():
+	pass
+
+
+class Inner:
+	var x
+
+
+	@warning_ignore(\"unused\")
+	# Why it's unused
+	func g():
+		pass
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn properties() {
+        let source = "\
+var a:
+    get = get_a  ,set=set_a
+var b: int:
+	get:
+		return 1
+";
+        let expected = "\
+var a:
+	get = get_a, set = set_a
+var b: int:
+	get:
+		return 1
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn control_flow() {
+        let source = "\
+func f(x):
+    if x>1:
+        pass
+    elif x<0 :
+        pass
+
+    else:
+        for i in range(x):
+            match i:
+                1,2:
+                    continue
+                _:
+                    break
+    while true: pass
+";
+        let expected = "\
+func f(x):
+	if x > 1:
+		pass
+	elif x < 0:
+		pass
+	else:
+		for i in range(x):
+			match i:
+				1, 2:
+					continue
+				_:
+					break
+	while true:
+		pass
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn comments() {
+        let source = "\
+# header comment
+extends Node
+
+## Docs for f.
+func f():   # trailing
+	# leading
+	var a=[
+		1, # one
+		2,
+	]
+	# end of f
+
+# before g
+func g():
+	pass
+	# end of g
+# end of file
+";
+        let expected = "\
+# header comment
+extends Node
+
+
+## Docs for f.
+func f(): # trailing
+	# leading
+	var a = [
+		1, # one
+		2,
+	]
+	# end of f
+
+
+# before g
+func g():
+	pass
+	# end of g
+
+
+# end of file
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn list_comments() {
+        let source = "\
+var a = [ # open
+	# before one
+	1,   # one
+	2 # two
+	# end
+]
+func f():
+	foo(a, # first
+		b)
+	var d = {\"a\": 1, # x
+	b = 2}
+	var e = [1 + # inside of an expression
+		2]
+";
+        let expected = "\
+var a = [ # open
+	# before one
+	1, # one
+	2, # two
+	# end
+]
+
+
+func f():
+	foo(
+		a, # first
+		b,
+	)
+	var d = {
+		\"a\": 1, # x
+		b = 2,
+	}
+	var e = [1 + # inside of an expression
+		2]
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn multiline_strings() {
+        let source = "func f():\n    var s = \"\"\"a  \n    b\n\"\"\"\n";
+        assert_eq!(
+            format(source),
+            "func f():\n\tvar s = \"\"\"a  \n    b\n\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn wrapping() {
+        let source = "\
+func f(first: int, second: String) -> void:
+	foo(first_argument, second_argument, 3)
+	if first_condition and second_condition:
+		return node.get_parent().get_child(0)
+	var a = [[1, 2], {\"key\": \"value\", \"other\": 3}]
+	var b = [
+		1,
+		2
+	]
+	var c = [1, 2,]
+";
+        let expected = "\
+func f(
+	first: int,
+	second: String,
+) -> void:
+	foo(
+		first_argument,
+		second_argument,
+		3,
+	)
+	if (
+		first_condition
+		and second_condition
+	):
+		return (
+			node
+			.get_parent()
+			.get_child(0)
+		)
+	var a = [
+		[1, 2],
+		{\"key\": \"value\", \"other\": 3},
+	]
+	var b = [1, 2]
+	var c = [
+		1,
+		2,
+	]
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn line_endings() {
+        assert_eq!(
+            format("func f():\r\n    pass\r\n"),
+            "func f():\r\n\tpass\r\n"
+        );
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+        assert!(matches!(
+            format_code(&mut parser, "func f(:\n", &FormatOptions::default()),
+            Err(FormatError::Syntax)
+        ));
+    }
+}
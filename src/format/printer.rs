@@ -0,0 +1,668 @@
+use std::ops::Range;
+
+use tree_sitter::Node;
+
+use super::{
+    FormatOptions,
+    layout::{self, Doc},
+    tree::{self, Block, Comments, Item, Unit},
+};
+use crate::line_index::LineIndex;
+
+fn is_opening(token: &str) -> bool {
+    matches!(token, "(" | "[" | "{")
+}
+
+fn is_closing(token: &str) -> bool {
+    matches!(token, ")" | "]" | "}")
+}
+
+/// Whether `token` is the operator of a unary expression that's written without a space.
+fn is_unary_symbol(token: Node) -> bool {
+    !token.is_named()
+        && matches!(token.kind(), "-" | "+" | "!" | "~")
+        && token
+            .parent()
+            .is_some_and(|parent| parent.kind() == "unary_operator")
+}
+
+/// Whether there's a space between two consecutive tokens.
+fn space_between(a: Node, b: Node) -> bool {
+    let (a_kind, b_kind) = (a.kind(), b.kind());
+    let a_token = !a.is_named();
+    let b_token = !b.is_named();
+
+    if b_token && (matches!(b_kind, "," | ";" | "." | ":") || is_closing(b_kind)) {
+        return false;
+    }
+    if a_token && (matches!(a_kind, "." | "@") || is_opening(a_kind)) {
+        return false;
+    }
+    // The `&` of a StringName and the `^` of a NodePath are part of the literal
+    if a_token
+        && matches!(a_kind, "&" | "^")
+        && a.parent()
+            .is_some_and(|parent| matches!(parent.kind(), "string_name" | "node_path"))
+    {
+        return false;
+    }
+    if b_token && matches!(b_kind, "(" | "[") {
+        // Calls and subscripts, but not e.g. `return [1, 2]` or `not (a or b)`
+        return !(a.is_named()
+            || (a_token && (is_closing(a_kind) || matches!(a_kind, "func" | "_init" | "set"))));
+    }
+    !is_unary_symbol(a)
+}
+
+/// Whether the token is a `,` right before a closing bracket.
+pub fn is_trailing_comma(token: Node) -> bool {
+    token.kind() == ","
+        && std::iter::successors(token.next_sibling(), |node| node.next_sibling())
+            .find(|node| node.kind() != "comment")
+            .is_some_and(|next| is_closing(next.kind()))
+}
+
+pub struct Printer<'a, 'tree> {
+    source: &'a str,
+    lines: &'a LineIndex,
+    indent_width: usize,
+    options: &'a FormatOptions,
+    comments: Comments<'tree>,
+    /// Ranges of strings spanning multiple lines, their content is never touched.
+    multiline_strings: Vec<Range<usize>>,
+    /// Ranges of lambdas spanning multiple lines, they're kept as written.
+    multiline_lambdas: Vec<Range<usize>>,
+    out: Vec<String>,
+    /// End of the last element that was printed, in the original source.
+    last_end: usize,
+}
+
+impl<'a, 'tree> Printer<'a, 'tree> {
+    pub fn new(
+        source: &'a str,
+        lines: &'a LineIndex,
+        indent_width: usize,
+        options: &'a FormatOptions,
+        comments: Comments<'tree>,
+        root: Node<'tree>,
+    ) -> Self {
+        let mut multiline_strings = Vec::new();
+        let mut multiline_lambdas = Vec::new();
+        collect_multiline(root, &mut multiline_strings, &mut multiline_lambdas);
+        Self {
+            source,
+            lines,
+            indent_width,
+            options,
+            comments,
+            multiline_strings,
+            multiline_lambdas,
+            out: Vec::new(),
+            last_end: 0,
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        while self.out.last().is_some_and(|line| line.is_empty()) {
+            self.out.pop();
+        }
+        let mut out = self.out.join("\n");
+        out.push('\n');
+        out
+    }
+
+    /// The number of blank lines between the last printed element and `offset` in the original.
+    fn blank_lines_before(&self, offset: usize) -> usize {
+        let from = self.lines.line(self.last_end) + 1;
+        let to = self.lines.line(offset);
+        (from..to)
+            .filter(|&row| {
+                let start = self.lines.line_start(row);
+                let end = self
+                    .lines
+                    .line_start((row + 1).min(self.lines.lines() - 1))
+                    .max(start);
+                self.source[start..end].trim().is_empty()
+            })
+            .count()
+    }
+
+    fn push_line(&mut self, blank_lines: usize, level: usize, text: &str) {
+        if !self.out.is_empty() {
+            self.out
+                .extend(std::iter::repeat_n(String::new(), blank_lines));
+        }
+        self.out.push(format!("{}{}", "\t".repeat(level), text));
+    }
+
+    fn comment(&mut self, comment: Node, blank_lines: usize, level: usize) {
+        let text = comment
+            .utf8_text(self.source.as_bytes())
+            .expect("valid utf8");
+        self.push_line(blank_lines, level, text.trim_end_matches('\r'));
+        self.last_end = comment.end_byte();
+    }
+
+    pub fn block(&mut self, block: &Block<'tree>, level: usize) {
+        let source = self.source.as_bytes();
+        let is_root = level == 0;
+
+        // Annotations on their own lines right before a definition belong to it, e.g. `@rpc`
+        let mut attached = vec![false; block.items.len()];
+        for (index, item) in block.items.iter().enumerate().rev() {
+            let next_is_definition = block
+                .items
+                .get(index + 1)
+                .is_some_and(|next| next.is_definition() || attached[index + 1]);
+            attached[index] =
+                item.node.kind() == "annotation" && !item.is_header(source) && next_is_definition;
+        }
+
+        let mut first = true;
+        let mut prev: Option<&Item> = None;
+        for (item_index, item) in block.items.iter().enumerate() {
+            let prev_item = prev.replace(item);
+            let prev_attached = item_index > 0 && attached[item_index - 1];
+            let after_header = is_root
+                && prev_item.is_some_and(|prev| prev.is_header(source))
+                && !item.is_header(source);
+            // `##` comments directly below the header document the class
+            let mut class_doc = after_header;
+            let mut first_of_item = true;
+
+            for (index, segment) in item.segments.iter().enumerate() {
+                let leading = self
+                    .comments
+                    .leading
+                    .remove(&segment.unit.range.start)
+                    .unwrap_or_default();
+                let elements = leading
+                    .iter()
+                    .map(|comment| (Some(*comment), comment.start_byte()))
+                    .chain(std::iter::once((None, segment.unit.range.start)));
+
+                for (comment, start) in elements {
+                    let original = self.blank_lines_before(start);
+                    let is_doc = comment.is_some_and(|comment| {
+                        comment
+                            .utf8_text(source)
+                            .is_ok_and(|text| text.starts_with("##"))
+                    });
+                    let around_definition = block.class_level
+                        && (item.is_definition()
+                            || attached[item_index]
+                            || prev_item.is_some_and(Item::is_definition));
+
+                    let blank_lines = if first || (class_doc && is_doc && original == 0) {
+                        0
+                    } else if prev_attached && first_of_item {
+                        // Like the annotations that are part of the definition's node
+                        match comment {
+                            Some(_) => original.min(1),
+                            None => 0,
+                        }
+                    } else if std::mem::take(&mut class_doc) {
+                        match around_definition {
+                            true => 2,
+                            false => 1,
+                        }
+                    } else if first_of_item {
+                        match around_definition {
+                            true => 2,
+                            false => original.min(1),
+                        }
+                    } else if index > 0 && comment.is_none() {
+                        // `elif`/`else` and friends stick to the previous segment
+                        0
+                    } else {
+                        original.min(1)
+                    };
+                    first = false;
+                    first_of_item = false;
+
+                    match comment {
+                        Some(comment) => self.comment(comment, blank_lines, level),
+                        None => self.unit(&segment.unit, blank_lines, level),
+                    }
+                }
+
+                if let Some(nested) = &segment.block {
+                    self.block(nested, level + 1);
+                }
+            }
+        }
+
+        for comment in self
+            .comments
+            .block_end
+            .remove(&block.id)
+            .unwrap_or_default()
+        {
+            let original = self.blank_lines_before(comment.start_byte());
+            let blank_lines = match prev.take() {
+                _ if first => 0,
+                Some(prev) if block.class_level && prev.is_definition() => 2,
+                _ => original.min(1),
+            };
+            first = false;
+            self.comment(comment, blank_lines, level);
+        }
+    }
+
+    fn unit(&mut self, unit: &Unit<'tree>, blank_lines: usize, level: usize) {
+        let source = self.source.as_bytes();
+
+        let overlaps =
+            |range: &Range<usize>| range.start < unit.range.end && unit.range.start < range.end;
+        let verbatim = self.comments.inner.contains(&unit.range.start)
+            || self.multiline_strings.iter().any(overlaps)
+            || self.multiline_lambdas.iter().any(overlaps);
+
+        let mut lines = match verbatim {
+            true => self.verbatim(unit, level),
+            false => {
+                let builder = DocBuilder {
+                    source,
+                    range: &unit.range,
+                };
+                let doc = match tree::is_atomic(unit.owner, source) {
+                    true => builder.text(unit.owner),
+                    false => builder.children(unit.owner, false),
+                };
+                layout::render(&doc, level, self.options.max_width, self.options.tab_width)
+            }
+        };
+
+        if let Some(trailing) = self.comments.trailing.remove(&unit.range.start) {
+            let last = lines.last_mut().expect("at least one line");
+            for comment in trailing {
+                let text = comment.utf8_text(source).expect("valid utf8");
+                last.push(' ');
+                last.push_str(text.trim_end_matches('\r'));
+                self.last_end = self.last_end.max(comment.end_byte());
+            }
+        }
+
+        let mut lines = lines.into_iter();
+        self.push_line(
+            blank_lines,
+            level,
+            &lines.next().expect("at least one line"),
+        );
+        self.out.extend(lines);
+        self.last_end = self.last_end.max(unit.range.end);
+    }
+
+    /// Keeps the unit as written, but re-indents the lines relative to its first line.
+    fn verbatim(&self, unit: &Unit<'tree>, level: usize) -> Vec<String> {
+        let start_row = self.lines.line(unit.range.start);
+        let base_level = tree::indent_level(
+            &self.source[self.lines.line_start(start_row)..],
+            self.indent_width,
+        );
+
+        let mut lines = Vec::new();
+        let mut offset = unit.range.start;
+        for (index, line) in self.source[unit.range.clone()].split('\n').enumerate() {
+            let line_end = offset + line.len();
+            let in_string = |at: usize| {
+                self.multiline_strings
+                    .iter()
+                    .any(|s| s.start < at && at < s.end)
+            };
+            // Trailing whitespace inside of a string is part of its content
+            let trim_end = |line: &'a str| match in_string(line_end) {
+                true => line.trim_end_matches('\r'),
+                false => line.trim_end(),
+            };
+
+            let text = if index == 0 || in_string(offset) {
+                trim_end(line).to_string()
+            } else if line.trim().is_empty() {
+                String::new()
+            } else {
+                let line_level = tree::indent_level(line, self.indent_width);
+                let new_level = (level + line_level).saturating_sub(base_level);
+                format!("{}{}", "\t".repeat(new_level), trim_end(line).trim_start())
+            };
+            lines.push(text);
+            offset += line.len() + 1;
+        }
+        lines
+    }
+}
+
+/// Collects the strings and lambdas that span multiple lines.
+fn collect_multiline(node: Node, strings: &mut Vec<Range<usize>>, lambdas: &mut Vec<Range<usize>>) {
+    let multiline = node.start_position().row != node.end_position().row;
+    match node.kind() {
+        "string" => {
+            if multiline {
+                strings.push(node.byte_range());
+            }
+            return;
+        }
+        "lambda" if multiline => lambdas.push(node.byte_range()),
+        _ => {}
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_multiline(child, strings, lambdas);
+    }
+}
+
+/// Bracketed lists that are split with one item per line.
+pub fn is_list(kind: &str) -> bool {
+    matches!(
+        kind,
+        "arguments"
+            | "parameters"
+            | "array"
+            | "dictionary"
+            | "enumerator_list"
+            | "pattern_array"
+            | "pattern_dictionary"
+            | "parenthesized_expression"
+    )
+}
+
+/// Whether a trailing comma is added to a list when it's split.
+fn allows_trailing_comma(kind: &str) -> bool {
+    !matches!(kind, "pattern_dictionary" | "parenthesized_expression")
+}
+
+/// `and` and `or` (or their symbol variants) of a binary operator.
+fn boolean_operator(node: Node) -> Option<&'static str> {
+    if node.kind() != "binary_operator" {
+        return None;
+    }
+    match node.child_by_field_name("op")?.kind() {
+        "and" | "&&" => Some("and"),
+        "or" | "||" => Some("or"),
+        _ => None,
+    }
+}
+
+/// Whether the node is an attribute with at least two method calls, e.g. `a.b().c()`.
+fn is_method_chain(node: Node) -> bool {
+    let mut cursor = node.walk();
+    node.kind() == "attribute"
+        && node
+            .children(&mut cursor)
+            .filter(|child| child.kind() == "attribute_call")
+            .count()
+            >= 2
+}
+
+/// Expressions of statements that get wrapped in parentheses when they're too long,
+/// as line breaks are only allowed inside of brackets.
+fn wants_implicit_parens(node: Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    (boolean_operator(node).is_some() || is_method_chain(node))
+        && matches!(
+            parent.kind(),
+            "variable_statement"
+                | "const_statement"
+                | "return_statement"
+                | "if_statement"
+                | "elif_clause"
+                | "while_statement"
+                | "for_statement"
+                | "match_statement"
+                | "assignment"
+                | "augmented_assignment"
+        )
+        && parent
+            .child_by_field_name("left")
+            .is_none_or(|left| left.id() != node.id())
+}
+
+/// Builds the [`Doc`] of a unit.
+struct DocBuilder<'a> {
+    source: &'a [u8],
+    range: &'a Range<usize>,
+}
+
+impl<'tree> DocBuilder<'_> {
+    fn in_range(&self, node: Node) -> bool {
+        node.end_byte() > self.range.start
+            && node.start_byte() < self.range.end
+            && node.kind() != "comment"
+    }
+
+    fn text(&self, node: Node) -> Doc {
+        Doc::text(node.utf8_text(self.source).expect("valid utf8"))
+    }
+
+    /// The first and last token of the node.
+    fn edges(&self, node: Node<'tree>) -> Option<(Node<'tree>, Node<'tree>)> {
+        if tree::is_atomic(node, self.source) {
+            return Some((node, node));
+        }
+        let mut tokens = Vec::new();
+        tree::tokens(node, self.range, self.source, &mut tokens);
+        Some((*tokens.first()?, *tokens.last()?))
+    }
+
+    /// `breakable` tells whether line breaks are allowed, i.e. whether the node is inside of brackets.
+    fn node(&self, node: Node<'tree>, breakable: bool) -> Doc {
+        if tree::is_atomic(node, self.source) {
+            self.text(node)
+        } else if node.kind() == "lambda" {
+            // The body of a single line lambda has to stay on the line of its parameters
+            self.flat(node)
+        } else if is_list(node.kind()) {
+            self.list(node)
+        } else if breakable && let Some(operator) = boolean_operator(node) {
+            self.boolean_chain(node, operator)
+        } else if breakable && is_method_chain(node) {
+            self.method_chain(node)
+        } else {
+            self.children(node, breakable)
+        }
+    }
+
+    /// The node on a single line, as written but with normalized spacing.
+    fn flat(&self, node: Node<'tree>) -> Doc {
+        let mut tokens = Vec::new();
+        tree::tokens(node, self.range, self.source, &mut tokens);
+        self.join(
+            tokens
+                .into_iter()
+                .map(|token| (self.text(token), (token, token))),
+        )
+    }
+
+    /// The children of the node, separated by spaces where needed.
+    fn children(&self, node: Node<'tree>, breakable: bool) -> Doc {
+        let mut cursor = node.walk();
+        let children = node
+            .children(&mut cursor)
+            .filter(|child| self.in_range(*child))
+            .collect::<Vec<_>>();
+
+        let pieces = children.into_iter().filter_map(|child| {
+            let doc = match !breakable && wants_implicit_parens(child) {
+                true => Doc::group(
+                    vec![
+                        Doc::IfBreak("("),
+                        Doc::indent(vec![Doc::SoftLine, self.node(child, true)]),
+                        Doc::SoftLine,
+                        Doc::IfBreak(")"),
+                    ],
+                    false,
+                ),
+                false => self.node(child, breakable),
+            };
+            Some((doc, self.edges(child)?))
+        });
+        self.join(pieces)
+    }
+
+    fn join(&self, pieces: impl IntoIterator<Item = (Doc, (Node<'tree>, Node<'tree>))>) -> Doc {
+        let mut docs = Vec::new();
+        let mut prev: Option<Node> = None;
+        for (doc, (first, last)) in pieces {
+            if prev.is_some_and(|prev| space_between(prev, first)) {
+                docs.push(Doc::text(" "));
+            }
+            docs.push(doc);
+            prev = Some(last);
+        }
+        Doc::Concat(docs)
+    }
+
+    /// A bracketed list, split with one item per line if it doesn't fit.
+    ///
+    /// Comments between the items split the list. A comment on the line of an item stays
+    /// behind it, a comment on its own line stays before the next item.
+    fn list(&self, node: Node<'tree>) -> Doc {
+        let mut cursor = node.walk();
+        let children = node
+            .children(&mut cursor)
+            .filter(|child| child.kind() != "comment")
+            .collect::<Vec<_>>();
+        let [open, items @ .., close] = &children[..] else {
+            return self.children(node, true);
+        };
+
+        let magic_trailing_comma = items.last().is_some_and(|last| is_trailing_comma(*last));
+        let items = items
+            .iter()
+            .filter(|item| item.kind() != ",")
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            return self.children(node, true);
+        }
+
+        // `trailing[0]` follows the opening bracket, `trailing[i + 1]` the item `i`.
+        // `leading[i]` precedes the item `i`, the last one the closing bracket.
+        let mut trailing = vec![Vec::new(); items.len() + 1];
+        let mut leading = vec![Vec::new(); items.len() + 1];
+        let mut seen = 0;
+        let mut prev = *open;
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.kind() != "comment" {
+                seen += usize::from(items.iter().any(|item| item.id() == child.id()));
+                prev = child;
+            } else if prev.end_position().row == child.start_position().row {
+                trailing[seen].push(child);
+            } else {
+                leading[seen].push(child);
+            }
+        }
+        let comment = |comment: &Node| {
+            let text = comment.utf8_text(self.source).expect("valid utf8");
+            Doc::text(text.trim_end_matches('\r'))
+        };
+        let has_comments = node
+            .named_children(&mut cursor)
+            .any(|child| child.kind() == "comment");
+
+        let mut inner = Vec::new();
+        for after_open in &trailing[0] {
+            inner.extend([Doc::text(" "), comment(after_open)]);
+        }
+        inner.push(Doc::SoftLine);
+        // Nothing may follow the `..` of an open pattern
+        let open_ending = items
+            .last()
+            .is_some_and(|item| item.kind() == "pattern_open_ending");
+        for (index, item) in items.iter().enumerate() {
+            for before in &leading[index] {
+                inner.extend([comment(before), Doc::Line]);
+            }
+            inner.push(self.node(**item, true));
+            let last = index + 1 == items.len();
+            if !last {
+                inner.push(Doc::text(","));
+            } else if allows_trailing_comma(node.kind()) && !open_ending {
+                inner.push(Doc::IfBreak(","));
+            }
+            for after in &trailing[index + 1] {
+                inner.extend([Doc::text(" "), comment(after)]);
+            }
+            if !last {
+                inner.push(Doc::Line);
+            }
+        }
+        for before_close in &leading[items.len()] {
+            inner.extend([Doc::Line, comment(before_close)]);
+        }
+
+        Doc::group(
+            vec![
+                self.text(*open),
+                Doc::indent(inner),
+                Doc::SoftLine,
+                self.text(*close),
+            ],
+            magic_trailing_comma || has_comments,
+        )
+    }
+
+    /// `a and b and c`, split before the operators.
+    fn boolean_chain(&self, node: Node<'tree>, operator: &str) -> Doc {
+        let mut operands = Vec::new();
+        let mut operators = Vec::new();
+        let mut current = node;
+        loop {
+            let (Some(left), Some(op), Some(right)) = (
+                current.child_by_field_name("left"),
+                current.child_by_field_name("op"),
+                current.child_by_field_name("right"),
+            ) else {
+                return self.children(node, true);
+            };
+            operands.push(right);
+            operators.push(op);
+            match boolean_operator(left) == Some(operator) {
+                true => current = left,
+                false => {
+                    operands.push(left);
+                    break;
+                }
+            }
+        }
+        operands.reverse();
+        operators.reverse();
+
+        let mut docs = vec![self.node(operands[0], true)];
+        for (op, operand) in operators.into_iter().zip(&operands[1..]) {
+            docs.extend([
+                Doc::Line,
+                self.text(op),
+                Doc::text(" "),
+                self.node(*operand, true),
+            ]);
+        }
+        Doc::group(docs, false)
+    }
+
+    /// `a.b().c()`, split before the dots of the method calls.
+    fn method_chain(&self, node: Node<'tree>) -> Doc {
+        let mut cursor = node.walk();
+        let children = node.children(&mut cursor).collect::<Vec<_>>();
+
+        let mut docs = Vec::new();
+        let mut segment = Vec::new();
+        for (index, child) in children.iter().enumerate() {
+            let starts_call = child.kind() == "."
+                && children
+                    .get(index + 1)
+                    .is_some_and(|next| next.kind() == "attribute_call");
+            if starts_call && !segment.is_empty() {
+                docs.push(self.join(std::mem::take(&mut segment)));
+                docs.push(Doc::SoftLine);
+            }
+            if let Some(edges) = self.edges(*child) {
+                segment.push((self.node(*child, true), edges));
+            }
+        }
+        docs.push(self.join(segment));
+        Doc::group(docs, false)
+    }
+}
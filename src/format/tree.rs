@@ -0,0 +1,500 @@
+//! Splits a GDScript syntax tree into the lines the formatter emits.
+//!
+//! A [`Block`] is an indented list of [`Item`]s (statements), an item consists of one or
+//! more [`Segment`]s. Each segment is a single logical line (a [`Unit`]) optionally
+//! followed by a nested block, e.g. `if a:` with its body, followed by `else:` with its body.
+//!
+//! Comments are extras in tree-sitter and end up in odd places of the tree, so they're
+//! attached by their position instead, see [`Comments`].
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use tree_sitter::Node;
+
+use super::printer;
+use crate::{line_index::LineIndex, node_ext::collect_comments};
+
+/// A single logical line of code, covering the tokens of `owner` inside of `range`.
+#[derive(Debug, Clone)]
+pub struct Unit<'tree> {
+    pub owner: Node<'tree>,
+    pub range: Range<usize>,
+}
+
+#[derive(Debug)]
+pub struct Segment<'tree> {
+    pub unit: Unit<'tree>,
+    pub block: Option<Block<'tree>>,
+}
+
+#[derive(Debug)]
+pub struct Item<'tree> {
+    pub node: Node<'tree>,
+    pub segments: Vec<Segment<'tree>>,
+}
+
+impl Item<'_> {
+    /// Functions and classes, they're surrounded by two blank lines.
+    pub fn is_definition(&self) -> bool {
+        matches!(
+            self.node.kind(),
+            "function_definition" | "constructor_definition" | "class_definition"
+        )
+    }
+
+    /// `@tool`, `class_name`, `extends` and friends at the very top of a script.
+    pub fn is_header(&self, source: &[u8]) -> bool {
+        match self.node.kind() {
+            "class_name_statement" | "extends_statement" => true,
+            "annotation" => self.node.named_child(0).is_some_and(|name| {
+                matches!(
+                    name.utf8_text(source),
+                    Ok("tool" | "icon" | "static_unload")
+                )
+            }),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Block<'tree> {
+    pub id: usize,
+    /// Blocks of the script or a class contain definitions, see [`Item::is_definition`].
+    pub class_level: bool,
+    pub items: Vec<Item<'tree>>,
+}
+
+struct BlockInfo {
+    parent: Option<usize>,
+    /// The indentation level of the block in the original source.
+    level: usize,
+}
+
+struct UnitInfo {
+    range: Range<usize>,
+    block: usize,
+}
+
+/// Where a comment ends up in the formatted output.
+#[derive(Debug, Default)]
+pub struct Comments<'tree> {
+    /// Own-line comments before a segment, keyed by the start of the segment.
+    pub leading: HashMap<usize, Vec<Node<'tree>>>,
+    /// Comments after the code on the same line, keyed by the start of the unit.
+    pub trailing: HashMap<usize, Vec<Node<'tree>>>,
+    /// Own-line comments after the last item of a block, keyed by the block id.
+    pub block_end: HashMap<usize, Vec<Node<'tree>>>,
+    /// Units with comments inside of them that aren't between list items, they're emitted as
+    /// written.
+    pub inner: HashSet<usize>,
+}
+
+/// Returns the indentation level of a line, `indent_width` spaces count as one level.
+pub fn indent_level(line: &str, indent_width: usize) -> usize {
+    let mut tabs = 0;
+    let mut spaces = 0;
+    for ch in line.chars() {
+        match ch {
+            '\t' => tabs += 1,
+            ' ' => spaces += 1,
+            _ => break,
+        }
+    }
+    tabs + spaces / indent_width.max(1)
+}
+
+/// The end of the `:` that precedes `container` in `owner`, e.g. the body of a function.
+fn header_end(owner: Node, container: Node) -> usize {
+    let mut cursor = owner.walk();
+    owner
+        .children(&mut cursor)
+        .take_while(|child| child.id() != container.id())
+        .filter(|child| child.kind() == ":")
+        .last()
+        .map_or(container.start_byte(), |colon| colon.end_byte())
+}
+
+/// The next sibling that isn't a comment.
+fn next_code_sibling(node: Node) -> Option<Node> {
+    std::iter::successors(node.next_sibling(), |node| node.next_sibling())
+        .find(|node| node.kind() != "comment")
+}
+
+/// Whether the node is printed as a single token, e.g. strings whose content isn't part of the tree.
+pub fn is_atomic(node: Node, source: &[u8]) -> bool {
+    if node.child_count() == 0 || node.kind() == "comment" {
+        return true;
+    }
+    // Anything but whitespace that isn't covered by a child
+    let mut offset = node.start_byte();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if !source[offset..child.start_byte()].trim_ascii().is_empty() {
+            return true;
+        }
+        offset = child.end_byte();
+    }
+    !source[offset..node.end_byte()].trim_ascii().is_empty()
+}
+
+/// Collects the tokens (leaves and atomic nodes) of `node` inside of `range`, without comments.
+pub fn tokens<'tree>(
+    node: Node<'tree>,
+    range: &Range<usize>,
+    source: &[u8],
+    tokens: &mut Vec<Node<'tree>>,
+) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.end_byte() <= range.start || child.start_byte() >= range.end {
+            continue;
+        }
+        if child.kind() == "comment" {
+            continue;
+        }
+        match is_atomic(child, source) {
+            true => tokens.push(child),
+            false => self::tokens(child, range, source, tokens),
+        }
+    }
+}
+
+pub struct Builder<'a> {
+    source: &'a str,
+    lines: &'a LineIndex,
+    indent_width: usize,
+    blocks: Vec<BlockInfo>,
+    units: Vec<UnitInfo>,
+    /// Maps the start of every segment to the block of its item.
+    segments: HashMap<usize, usize>,
+}
+
+impl<'a> Builder<'a> {
+    pub fn new(source: &'a str, lines: &'a LineIndex, indent_width: usize) -> Self {
+        Self {
+            source,
+            lines,
+            indent_width,
+            blocks: Vec::new(),
+            units: Vec::new(),
+            segments: HashMap::new(),
+        }
+    }
+
+    fn level_at(&self, offset: usize) -> usize {
+        let start = self.lines.line_start(self.lines.line(offset));
+        indent_level(&self.source[start..], self.indent_width)
+    }
+
+    pub fn block<'tree>(
+        &mut self,
+        owner: Node<'tree>,
+        container: Node<'tree>,
+        parent: Option<usize>,
+    ) -> Block<'tree> {
+        let id = self.blocks.len();
+        let mut cursor = container.walk();
+        let children = container
+            .named_children(&mut cursor)
+            .filter(|child| child.kind() != "comment")
+            .collect::<Vec<_>>();
+
+        let level = match parent {
+            None => 0,
+            Some(_) => children
+                .first()
+                .map(|child| self.level_at(child.start_byte()))
+                .unwrap_or_default(),
+        };
+        self.blocks.push(BlockInfo { parent, level });
+
+        let items = children
+            .into_iter()
+            .map(|child| self.item(child, id))
+            .collect();
+
+        Block {
+            id,
+            class_level: matches!(owner.kind(), "source" | "class_definition"),
+            items,
+        }
+    }
+
+    fn unit<'tree>(
+        &mut self,
+        owner: Node<'tree>,
+        range: Range<usize>,
+        block: usize,
+    ) -> Unit<'tree> {
+        self.units.push(UnitInfo {
+            range: range.clone(),
+            block,
+        });
+        Unit { owner, range }
+    }
+
+    /// A segment without a nested block.
+    fn line<'tree>(
+        &mut self,
+        owner: Node<'tree>,
+        range: Range<usize>,
+        block: usize,
+    ) -> Segment<'tree> {
+        self.segments.insert(range.start, block);
+        Segment {
+            unit: self.unit(owner, range, block),
+            block: None,
+        }
+    }
+
+    /// A segment ending in a `:` followed by `container` as the nested block.
+    fn compound<'tree>(
+        &mut self,
+        owner: Node<'tree>,
+        range: Range<usize>,
+        container: Node<'tree>,
+        block: usize,
+    ) -> Segment<'tree> {
+        self.segments.insert(range.start, block);
+        let unit = self.unit(owner, range, block);
+        Segment {
+            unit,
+            block: Some(self.block(owner, container, Some(block))),
+        }
+    }
+
+    /// A segment ending in the `:` of a property, followed by its accessors on their own line,
+    /// e.g. `get = get_prop, set = set_prop`.
+    fn accessors<'tree>(
+        &mut self,
+        owner: Node<'tree>,
+        range: Range<usize>,
+        setget: Node<'tree>,
+        start: usize,
+        block: usize,
+    ) -> Segment<'tree> {
+        self.segments.insert(range.start, block);
+        let unit = self.unit(owner, range, block);
+
+        let id = self.blocks.len();
+        let level = self.level_at(start);
+        self.blocks.push(BlockInfo {
+            parent: Some(block),
+            level,
+        });
+        let segment = self.line(setget, start..setget.end_byte(), id);
+        let nested = Block {
+            id,
+            class_level: false,
+            items: vec![Item {
+                node: setget,
+                segments: vec![segment],
+            }],
+        };
+        Segment {
+            unit,
+            block: Some(nested),
+        }
+    }
+
+    fn item<'tree>(&mut self, node: Node<'tree>, block: usize) -> Item<'tree> {
+        let mut segments = Vec::new();
+        let mut start = node.start_byte();
+
+        // Annotations on their own lines are split off into separate segments
+        let mut cursor = node.walk();
+        let annotations = node
+            .children(&mut cursor)
+            .find(|child| child.kind() == "annotations");
+        if let Some(annotations) = annotations {
+            let mut cursor = annotations.walk();
+            for annotation in annotations.named_children(&mut cursor) {
+                let next = next_code_sibling(annotation).or_else(|| next_code_sibling(annotations));
+                let ends_line = next
+                    .is_some_and(|next| next.start_position().row > annotation.end_position().row);
+                if ends_line {
+                    segments.push(self.line(node, start..annotation.end_byte(), block));
+                    start = next.map_or(annotation.end_byte(), |next| next.start_byte());
+                }
+            }
+        }
+
+        // Not e.g. the `{...}` of an enum, which is also a `body`
+        let body = node
+            .child_by_field_name("body")
+            .or_else(|| {
+                let mut cursor = node.walk();
+                node.children(&mut cursor)
+                    .find(|child| child.kind() == "body")
+            })
+            .filter(|body| matches!(body.kind(), "body" | "class_body" | "match_body"));
+        let setget = node
+            .child_by_field_name("setget")
+            .filter(|setget| setget.child(0).is_some_and(|child| child.kind() == ":"));
+
+        let inline_accessors = setget.is_some_and(|setget| {
+            let mut cursor = setget.walk();
+            setget
+                .named_children(&mut cursor)
+                .all(|child| matches!(child.kind(), "getter" | "setter" | "comment"))
+        });
+
+        match (node.kind(), body, setget) {
+            // Properties with `get = getter, set = setter` instead of blocks
+            ("variable_statement", _, Some(setget)) if inline_accessors => {
+                let colon = setget.child(0).expect("the setget starts with a colon");
+                match next_code_sibling(colon) {
+                    Some(first) if first.start_position().row > colon.end_position().row => {
+                        let range = start..colon.end_byte();
+                        let first = first.start_byte();
+                        segments.push(self.accessors(node, range, setget, first, block));
+                    }
+                    _ => segments.push(self.line(node, start..node.end_byte(), block)),
+                }
+            }
+            // Properties with a `get:`/`set:` block
+            ("variable_statement", _, Some(setget)) => {
+                let range = start..setget.child(0).map_or(start, |colon| colon.end_byte());
+                segments.push(self.compound(node, range, setget, block));
+            }
+            ("if_statement", Some(body), _) => {
+                segments.push(self.compound(node, start..header_end(node, body), body, block));
+                let mut cursor = node.walk();
+                let alternatives = node
+                    .children_by_field_name("alternative", &mut cursor)
+                    .collect::<Vec<_>>();
+                for clause in alternatives {
+                    let segment = match clause.child_by_field_name("body") {
+                        Some(body) => {
+                            let range = clause.start_byte()..header_end(clause, body);
+                            self.compound(clause, range, body, block)
+                        }
+                        None => self.line(clause, clause.byte_range(), block),
+                    };
+                    segments.push(segment);
+                }
+            }
+            (_, Some(body), _) => {
+                segments.push(self.compound(node, start..header_end(node, body), body, block))
+            }
+            _ => segments.push(self.line(node, start..node.end_byte(), block)),
+        }
+
+        Item { node, segments }
+    }
+
+    /// Decides where every comment goes.
+    pub fn comments<'tree>(&self, root: Node<'tree>) -> Comments<'tree> {
+        let source = self.source.as_bytes();
+
+        let mut all = Vec::new();
+        collect_comments(root, &mut all);
+
+        let mut leaves = Vec::new();
+        tokens(root, &(0..source.len()), source, &mut leaves);
+        leaves.retain(|leaf| leaf.kind() != ";");
+
+        let mut units = self.units.iter().collect::<Vec<_>>();
+        units.sort_by_key(|unit| unit.range.start);
+        let unit_at = |offset: usize| {
+            let i = units.partition_point(|unit| unit.range.start <= offset);
+            units[..i]
+                .last()
+                .filter(|unit| offset < unit.range.end)
+                .copied()
+        };
+
+        let mut comments = Comments::default();
+        for comment in all {
+            let start = comment.start_byte();
+
+            if let Some(unit) = unit_at(start).filter(|unit| unit.range.start < start) {
+                // Comments between the items of a list are laid out with the items
+                if !is_list_comment(comment) {
+                    comments.inner.insert(unit.range.start);
+                }
+                continue;
+            }
+
+            let prev = leaves[..leaves.partition_point(|leaf| leaf.end_byte() <= start)].last();
+            let next_index = leaves.partition_point(|leaf| leaf.start_byte() < comment.end_byte());
+            let next = leaves.get(next_index);
+
+            let prev_unit = prev.and_then(|prev| unit_at(prev.start_byte()));
+            if let (Some(prev), Some(unit)) = (prev, prev_unit)
+                && prev.end_position().row == comment.start_position().row
+            {
+                comments
+                    .trailing
+                    .entry(unit.range.start)
+                    .or_default()
+                    .push(comment);
+                continue;
+            }
+
+            let next_block = next.and_then(|next| {
+                let start = next.start_byte();
+                self.segments.get(&start).map(|&block| (start, block))
+            });
+            let level = self.level_at(start);
+
+            let target = match (prev_unit, next_block) {
+                (None, Some((segment, _))) => Target::Leading(segment),
+                (None, None) => Target::BlockEnd(0),
+                (Some(unit), next_block) => {
+                    let chain =
+                        std::iter::successors(Some(unit.block), |&block| self.blocks[block].parent)
+                            .collect::<Vec<_>>();
+
+                    match next_block {
+                        // The next segment is the first one in a nested block
+                        Some((segment, block)) if !chain.contains(&block) => {
+                            Target::Leading(segment)
+                        }
+                        _ => chain
+                            .iter()
+                            .find_map(|&block| match next_block {
+                                Some((segment, next)) if next == block => {
+                                    Some(Target::Leading(segment))
+                                }
+                                _ if self.blocks[block].level <= level => {
+                                    Some(Target::BlockEnd(block))
+                                }
+                                _ => None,
+                            })
+                            .unwrap_or(Target::BlockEnd(0)),
+                    }
+                }
+            };
+
+            match target {
+                Target::Leading(segment) => comments.leading.entry(segment),
+                Target::BlockEnd(block) => comments.block_end.entry(block),
+            }
+            .or_default()
+            .push(comment);
+        }
+
+        comments
+    }
+}
+
+enum Target {
+    Leading(usize),
+    BlockEnd(usize),
+}
+
+/// Whether the comment is between the items of a list that has items.
+fn is_list_comment(comment: Node) -> bool {
+    comment.parent().is_some_and(|list| {
+        let mut cursor = list.walk();
+        printer::is_list(list.kind())
+            && list
+                .named_children(&mut cursor)
+                .any(|child| child.kind() != "comment")
+    })
+}
@@ -0,0 +1,645 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use baseline::{Baseline, BaselineWriter};
+use cache::Cache;
+use config::{Config, RuleConfigs};
+use lint::fix::{self, Applicability};
+use miette::Report;
+use node_ext::NodeExt;
+use owo_colors::OwoColorize;
+use summary::{ExitStatus, Summary};
+
+mod baseline;
+mod cache;
+mod changes;
+pub mod cli;
+pub mod config;
+mod document;
+mod files;
+pub mod format;
+mod line_index;
+mod lint;
+pub mod lsp;
+mod node_ext;
+mod output;
+mod parallel;
+mod query_struct;
+pub mod summary;
+mod util;
+mod watch;
+
+pub fn run_checks(
+    root: tree_sitter::Node,
+    source: Arc<str>,
+    rules: &RuleConfigs,
+    path: &Path,
+) -> Vec<Report> {
+    assert!(root.kind() == "source", "Expected 'source' node");
+
+    let mut suppressions = lint::Suppressions::parse(root, &source);
+
+    let mut reports = Vec::new();
+    let mut ran = Vec::new();
+    for rule in lint::RULES {
+        let Some(ctx) = lint::RuleContext::new(rule, rules.get(rule.code), path) else {
+            continue;
+        };
+        reports.extend((rule.check)(root, Arc::clone(&source), &ctx));
+        ran.push(rule.code);
+    }
+
+    // Suppressions are applied first, so the reports that are dropped below still use them
+    let reports = suppressions.apply(reports);
+
+    // Syntax errors can't be suppressed, but they silence the rules on their lines, including
+    // the unused suppressions
+    let syntax_errors = lint::syntax::SyntaxErrors::check(root, Arc::clone(&source));
+    let mut reports = syntax_errors.retain_outside(reports);
+
+    let unused = &lint::UNUSED_SUPPRESSION;
+    if let Some(ctx) = lint::RuleContext::new(unused, rules.get(unused.code), path) {
+        let unused = suppressions.unused(Arc::clone(&source), &ctx, |code| {
+            lint::rule_for_code(code).is_some_and(|rule| ran.contains(&rule.code))
+        });
+        reports.extend(syntax_errors.retain_outside(unused));
+    }
+    reports.extend(syntax_errors.reports);
+    reports
+}
+
+pub fn new_parser() -> anyhow::Result<tree_sitter::Parser> {
+    let mut parser = tree_sitter::Parser::new();
+    parser
+        .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+        .context("setting tree-sitter language")?;
+    Ok(parser)
+}
+
+/// How often fixes are applied to a file before giving up on reaching a fixed point.
+const MAX_FIX_ITERATIONS: usize = 10;
+
+fn check_source(
+    parser: &mut tree_sitter::Parser,
+    rules: &RuleConfigs,
+    content: Arc<str>,
+    path: &Path,
+) -> anyhow::Result<Vec<Report>> {
+    let start = std::time::Instant::now();
+
+    // parse
+    let tree = parser
+        .parse(content.as_bytes(), None)
+        .context("failed to parse file")?;
+    let parse_duration = start.elapsed();
+
+    tracing::trace!("File SExp: {}", tree.root_node().to_sexp());
+
+    // process
+    let reports = run_checks(tree.root_node(), content, rules, path);
+    let process_duration = start.elapsed() - parse_duration;
+
+    tracing::debug!(
+        "{}: Parsing: {:?}; Processing: {:?}; Total: {:?}",
+        path.display(),
+        parse_duration,
+        process_duration,
+        parse_duration + process_duration
+    );
+
+    Ok(reports)
+}
+
+/// Like [`check_source`], but takes the reports from the cache if they're cached.
+///
+/// Returns the cache key of the reports if they have to be added to the cache.
+fn check_source_cached(
+    parser: &mut tree_sitter::Parser,
+    rules: &RuleConfigs,
+    content: Arc<str>,
+    path: &Path,
+    cache: Option<&Cache>,
+) -> anyhow::Result<(Vec<Report>, Option<u64>)> {
+    let Some(cache) = cache else {
+        return Ok((check_source(parser, rules, content, path)?, None));
+    };
+    let key = Cache::key(rules, path, &content);
+    if let Some(reports) = cache.get(path, key, &content) {
+        return Ok((reports, None));
+    }
+    Ok((check_source(parser, rules, content, path)?, Some(key)))
+}
+
+/// The outcome of linting a single file.
+struct Linted {
+    /// The content the reports refer to, after applying fixes.
+    source: Arc<str>,
+    reports: Vec<Report>,
+    /// The number of applied fixes.
+    fixed: usize,
+    /// Set if the reports weren't cached yet.
+    cache_key: Option<u64>,
+}
+
+/// Lints a file, applying fixes up to the given applicability.
+fn lint_file(
+    parser: &mut tree_sitter::Parser,
+    config: &Config,
+    path: &Path,
+    fix: Option<Applicability>,
+    cache: Option<&Cache>,
+) -> anyhow::Result<Linted> {
+    let content = std::fs::read_to_string(path)
+        .map(Arc::<str>::from)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let linted = lint_source(parser, &config.rules_for(path), path, content, fix, cache)?;
+    if linted.fixed > 0 {
+        files::write_atomic(path, &linted.source)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(linted)
+}
+
+/// Lints the content of the file at `path`, the fixes are only applied to the returned source.
+fn lint_source(
+    parser: &mut tree_sitter::Parser,
+    rules: &RuleConfigs,
+    path: &Path,
+    mut content: Arc<str>,
+    fix: Option<Applicability>,
+    cache: Option<&Cache>,
+) -> anyhow::Result<Linted> {
+    let (mut reports, mut cache_key) =
+        check_source_cached(parser, rules, Arc::clone(&content), path, cache)?;
+
+    // Fixes are computed on a tree with recovered errors, they could make things worse
+    let Some(max_applicability) = fix.filter(|_| !lint::syntax::has_syntax_error(&reports)) else {
+        return Ok(Linted {
+            source: content,
+            reports,
+            fixed: 0,
+            cache_key,
+        });
+    };
+
+    // Fixes may overlap or enable other fixes, so apply them until nothing changes anymore
+    let mut fixed = 0;
+    for _ in 0..MAX_FIX_ITERATIONS {
+        let fixes = reports
+            .iter()
+            .filter_map(fix::fix_of)
+            .filter(|fix| fix.applicability <= max_applicability);
+        let (fixed_content, applied) = fix::apply_fixes(&content, fixes);
+        if applied == 0 || *fixed_content == *content {
+            break;
+        }
+
+        let fixed_content = Arc::<str>::from(fixed_content);
+        match check_source_cached(parser, rules, Arc::clone(&fixed_content), path, cache) {
+            Ok((fixed_reports, _)) if lint::syntax::has_syntax_error(&fixed_reports) => {
+                tracing::error!("fixes broke {}, not applying them", path.display());
+                break;
+            }
+            Ok((fixed_reports, fixed_cache_key)) => {
+                content = fixed_content;
+                reports = fixed_reports;
+                cache_key = fixed_cache_key;
+                fixed += applied;
+            }
+            Err(err) => {
+                tracing::error!("fixes broke {}, not applying them: {err:#}", path.display());
+                break;
+            }
+        }
+    }
+
+    Ok(Linted {
+        source: content,
+        reports,
+        fixed,
+        cache_key,
+    })
+}
+
+/// Finds the configuration, relative to `--stdin-filename` if it's given.
+fn find_config(cli: &cli::Cli, args: &cli::FileArgs) -> anyhow::Result<Config> {
+    let config = match &args.stdin_filename {
+        Some(path) => Config::find_for(cli.config.as_deref(), path)?,
+        None => Config::find(cli.config.as_deref())?,
+    };
+    if let Some(path) = &config.path {
+        tracing::info!("Using configuration from {}", path.display());
+    }
+    Ok(config)
+}
+
+/// Reads the script for `--stdin`, returns the path it's treated as.
+fn read_stdin(args: &cli::FileArgs) -> anyhow::Result<(PathBuf, Arc<str>)> {
+    let mut content = String::new();
+    std::io::stdin()
+        .read_to_string(&mut content)
+        .context("failed to read stdin")?;
+    let path = args
+        .stdin_filename
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(files::STDIN_PATH));
+    Ok((path, content.into()))
+}
+
+pub fn lint(cli: &cli::Cli, args: &cli::LintArgs) -> anyhow::Result<ExitStatus> {
+    let config = find_config(cli, &args.files)?;
+
+    let stdin = args
+        .files
+        .stdin
+        .then(|| read_stdin(&args.files))
+        .transpose()?;
+    let mut files = match &stdin {
+        Some((path, _)) => vec![path.clone()],
+        None => files::collect_files(&args.files)?,
+    };
+    let changes = match (&args.diff_base, args.changed_only) {
+        (Some(base), _) => Some(changes::Changes::since(base)?),
+        (None, true) => Some(changes::Changes::since("HEAD")?),
+        (None, false) => None,
+    };
+    if let Some(changes) = &changes {
+        files.retain(|path| changes.touches(path));
+    }
+    tracing::info!("Linting {} files...", files.len());
+
+    let fix = match (args.fix_unsafe, args.fix) {
+        (true, _) => Some(Applicability::Unsafe),
+        (false, true) => Some(Applicability::Safe),
+        (false, false) => None,
+    };
+
+    // Fail early, the threads create their own parsers
+    new_parser()?;
+    let jobs = args.jobs.unwrap_or_else(parallel::default_jobs);
+
+    // The cache is keyed by the content of files, a script from stdin isn't worth caching
+    let cache = (!args.no_cache && stdin.is_none()).then(|| Cache::load(config.root()));
+    let mut uncached = Vec::new();
+
+    // Without a path the baseline is next to `foli.toml`
+    let baseline_path = |path: &Option<PathBuf>| {
+        path.clone()
+            .unwrap_or_else(|| config.root().join(baseline::DEFAULT_PATH))
+    };
+    let mut baseline = args
+        .baseline
+        .as_ref()
+        .map(|path| Baseline::load(&baseline_path(path)))
+        .transpose()?;
+    let mut baseline_writer = args
+        .write_baseline
+        .as_ref()
+        .map(|path| BaselineWriter::new(&baseline_path(path)));
+
+    if args.watch {
+        return watch::watch(cli, args, config, &files, baseline, jobs);
+    }
+
+    let mut emitter = output::emitter(args.format, std::io::stdout().lock());
+    let mut output_error = None;
+    let mut summary = Summary::default();
+    parallel::map_ordered(
+        &files,
+        jobs,
+        || new_parser().expect("the parser was created before"),
+        |parser, path| match &stdin {
+            Some((_, content)) => {
+                let mut rules = config.rules_for(path);
+                // Without a file name there's nothing to check
+                if args.files.stdin_filename.is_none() {
+                    rules.entry("file-name".to_string()).or_default().enabled = Some(false);
+                }
+                lint_source(parser, &rules, path, Arc::clone(content), None, None)
+            }
+            None => lint_file(parser, &config, path, fix, cache.as_ref()),
+        },
+        |path, result| {
+            let linted = match result {
+                Ok(linted) => linted,
+                Err(err) => {
+                    tracing::error!("{err:#}");
+                    summary.record_failure();
+                    return;
+                }
+            };
+            summary.fixed += linted.fixed;
+            if let Some(key) = linted.cache_key {
+                uncached.push((path, cache::Entry::new(key, &linted.reports)));
+            }
+
+            if let Some(writer) = &mut baseline_writer {
+                writer.record(path, &linted.source, &linted.reports);
+                return;
+            }
+            let mut reports = match &mut baseline {
+                Some(baseline) => baseline.filter(path, &linted.source, linted.reports),
+                None => linted.reports,
+            };
+            // After the baseline, otherwise its entries on other lines would count as fixed
+            if let Some(changes) = &changes {
+                reports = changes.filter(path, &linted.source, reports);
+            }
+
+            if output_error.is_none()
+                && let Err(err) = emitter.file(path, &linted.source, &reports)
+            {
+                output_error = Some(err);
+            }
+            summary.record(&reports);
+        },
+    );
+    match output_error {
+        Some(err) => Err(err),
+        None => emitter.finish(),
+    }
+    .context("failed to write the diagnostics")?;
+
+    if let Some(mut cache) = cache {
+        tracing::debug!(
+            "{} of {} files were cached",
+            files.len() - uncached.len(),
+            files.len()
+        );
+        for (path, entry) in uncached {
+            cache.insert(path, entry);
+        }
+        // The diagnostics are complete, a cache that can't be written isn't worth failing over
+        if let Err(err) = cache.save() {
+            tracing::warn!("{err:#}");
+        }
+    }
+
+    if let Some(writer) = baseline_writer {
+        let count = writer.save()?;
+        eprintln!(
+            "Recorded {count} diagnostics of {} files in the baseline.",
+            files.len()
+        );
+        return Ok(ExitStatus::Clean);
+    }
+    if let Some(baseline) = &baseline {
+        let fixed = baseline.fixed();
+        if !fixed.is_empty() {
+            eprintln!("{} diagnostics of the baseline are fixed:", fixed.len());
+            for entry in fixed {
+                eprintln!("  {}  {}", entry.path, entry.code);
+            }
+            eprintln!("Run with `--write-baseline` to remove them from the baseline.");
+        }
+    }
+
+    summary.print(stdin.is_none());
+    Ok(summary.exit_status(args.max_warnings))
+}
+
+pub fn cache(command: &cli::CacheCommand, cli: &cli::Cli) -> anyhow::Result<ExitStatus> {
+    match command {
+        cli::CacheCommand::Clean => {
+            let config = Config::find(cli.config.as_deref())?;
+            match cache::clean(config.root())? {
+                true => eprintln!("Removed {}", config.root().join(cache::CACHE_DIR).display()),
+                false => eprintln!("There is no cache in {}", config.root().display()),
+            }
+            Ok(ExitStatus::Clean)
+        }
+    }
+}
+
+/// Formats a file, returns what to print if it's (or would be) changed.
+///
+/// The output is printed by the caller, so it isn't interleaved with the output of other files.
+fn format_file(
+    parser: &mut tree_sitter::Parser,
+    args: &cli::FormatArgs,
+    options: &format::FormatOptions,
+    path: &Path,
+) -> anyhow::Result<Option<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let formatted = format::format_code(parser, &content, options)
+        .with_context(|| format!("failed to format {}", path.display()))?;
+
+    if formatted == content {
+        return Ok(None);
+    }
+
+    if args.diff {
+        Ok(Some(format::unified_diff(
+            path,
+            &content,
+            &formatted,
+            format::use_color(),
+        )))
+    } else if args.check {
+        Ok(Some(format!("Would reformat {}\n", path.display().red())))
+    } else {
+        // Unchanged files aren't touched, so Godot doesn't reload them
+        files::write_atomic(path, &formatted)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(Some(String::new()))
+    }
+}
+
+/// Formats the script from stdin, prints the formatted script (or the diff) to stdout.
+fn format_stdin(
+    args: &cli::FormatArgs,
+    options: &format::FormatOptions,
+) -> anyhow::Result<ExitStatus> {
+    let (path, content) = read_stdin(&args.files)?;
+    let formatted = format::format_code(&mut new_parser()?, &content, options)
+        .with_context(|| format!("failed to format {}", path.display()))?;
+
+    let changed = formatted != *content;
+    if args.diff {
+        if changed {
+            let diff = format::unified_diff(&path, &content, &formatted, format::use_color());
+            print!("{diff}");
+        }
+    } else if args.check {
+        if changed {
+            eprintln!("Would reformat {}", path.display().red());
+        }
+    } else {
+        print!("{formatted}");
+    }
+
+    match changed && (args.check || args.diff) {
+        true => Ok(ExitStatus::Warnings),
+        false => Ok(ExitStatus::Clean),
+    }
+}
+
+pub fn format(cli: &cli::Cli, args: &cli::FormatArgs) -> anyhow::Result<ExitStatus> {
+    let config = find_config(cli, &args.files)?;
+    let options = format::FormatOptions {
+        max_width: args.max_width.unwrap_or(config.format.max_width),
+        ..config.format
+    };
+    if args.files.stdin {
+        return format_stdin(args, &options);
+    }
+
+    let files = files::collect_files(&args.files)?;
+    tracing::info!("Formatting {} files...", files.len());
+
+    // Fail early, the threads create their own parsers
+    new_parser()?;
+    let jobs = args.jobs.unwrap_or_else(parallel::default_jobs);
+
+    let mut changed = 0;
+    let mut failures = 0;
+    parallel::map_ordered(
+        &files,
+        jobs,
+        || new_parser().expect("the parser was created before"),
+        |parser, path| format_file(parser, args, &options, path),
+        |_, result| match result {
+            Ok(Some(output)) => {
+                print!("{output}");
+                changed += 1;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                tracing::error!("{err:#}");
+                failures += 1;
+            }
+        },
+    );
+
+    let dry_run = args.check || args.diff;
+    let unchanged = files.len() - changed - failures;
+    match dry_run {
+        true => {
+            eprintln!("{changed} files would be reformatted, {unchanged} files already formatted")
+        }
+        false => eprintln!("{changed} files reformatted, {unchanged} files left unchanged"),
+    }
+
+    if failures > 0 {
+        eprintln!("💥 Failed to format {failures} files.");
+        Ok(ExitStatus::Failure)
+    } else if dry_run && changed > 0 {
+        Ok(ExitStatus::Warnings)
+    } else {
+        Ok(ExitStatus::Clean)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser as _;
+    use tree_sitter::{Parser, Query, QueryCursor, StreamingIterator};
+
+    use super::{ExitStatus, cli};
+
+    const CODE_1: &str = r#"var a := foo(1, 1 + 1, 2)"#;
+    const CODE_2: &str = r#"var a := foo()"#;
+    const QUERY: &str = r#"
+        (variable_statement
+            value: (call (_) @fn
+                (arguments (_)? @cap)))
+    "#;
+
+    #[test]
+    fn multi_capture_some() {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+
+        let tree = parser.parse(CODE_1.as_bytes(), None).unwrap();
+
+        let query = Query::new(&tree_sitter_gdscript::LANGUAGE.into(), QUERY).unwrap();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&query, tree.root_node(), CODE_1.as_bytes());
+
+        let mut results = Vec::new();
+        while let Some(match_) = matches.next() {
+            for capture in match_.captures {
+                results.push((match_.id(), capture.index, capture.node.byte_range()));
+            }
+        }
+
+        assert_eq!(
+            results,
+            vec![
+                (0, 0, 9..12),  // Match 0, Capture 0: "foo"
+                (0, 1, 13..14), // Match 0, Capture 1: "1"
+                (1, 0, 9..12),  // Match 1, Capture 0: "foo"
+                (1, 1, 16..21), // Match 1, Capture 1: "1 + 1"
+                (2, 0, 9..12),  // Match 2, Capture 0: "foo"
+                (2, 1, 23..24)  // Match 2, Capture 1: "2"
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_capture_none() {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+
+        let tree = parser.parse(CODE_2.as_bytes(), None).unwrap();
+
+        let query = Query::new(&tree_sitter_gdscript::LANGUAGE.into(), QUERY).unwrap();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(&query, tree.root_node(), CODE_2.as_bytes());
+
+        let mut results = Vec::new();
+        while let Some(match_) = matches.next() {
+            for capture in match_.captures {
+                results.push((match_.id(), capture.index, capture.node.byte_range()));
+            }
+        }
+
+        assert_eq!(
+            results,
+            vec![
+                (0, 0, 9..12) // Match 0, Capture 0: "foo"
+            ]
+        );
+    }
+
+    #[test]
+    fn format_exit_codes() {
+        let dir = std::env::temp_dir().join(format!("foli-format-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let formatted = dir.join("formatted.gd");
+        let unformatted = dir.join("unformatted.gd");
+        let broken = dir.join("broken.gd");
+        std::fs::write(&formatted, "var a = 1\n").unwrap();
+        std::fs::write(&unformatted, "var a=1\n").unwrap();
+        std::fs::write(&broken, "func f(:\n").unwrap();
+
+        let format = |flag: &str, path: &std::path::Path| {
+            let cli =
+                cli::Cli::parse_from(["gdscript-foli", "format", flag, path.to_str().unwrap()]);
+            let cli::Command::Format(args) = &cli.command else {
+                unreachable!()
+            };
+            super::format(&cli, args).unwrap()
+        };
+        for flag in ["--check", "--diff"] {
+            assert_eq!(format(flag, &formatted), ExitStatus::Clean);
+            assert_eq!(format(flag, &unformatted), ExitStatus::Warnings);
+            assert_eq!(format(flag, &broken), ExitStatus::Failure);
+        }
+        assert_eq!(ExitStatus::Warnings as u8, 1);
+        assert_eq!(ExitStatus::Failure as u8, 3);
+        // Neither flag writes the file
+        assert_eq!(std::fs::read_to_string(&unformatted).unwrap(), "var a=1\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+//! Maps byte offsets to lines and columns.
+
+use serde::Serialize;
+
+/// Byte offsets of the line starts, to map offsets to (zero-based) lines.
+pub struct LineIndex(Vec<usize>);
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self(starts)
+    }
+
+    /// The number of lines, a trailing newline starts an empty last line.
+    pub fn lines(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn line(&self, offset: usize) -> usize {
+        self.0.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// The offset of the first character of the (zero-based) line.
+    pub fn line_start(&self, line: usize) -> usize {
+        self.0[line]
+    }
+
+    /// The position of the offset like editors show it.
+    pub fn position(&self, source: &str, offset: usize) -> Position {
+        let line = self.line(offset);
+        let start = self.0[line];
+        Position {
+            line: line + 1,
+            column: source[start..offset].chars().count() + 1,
+        }
+    }
+}
+
+/// A one-based line and column, columns are counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let source = "a\nbä c\n";
+        let lines = LineIndex::new(source);
+        assert_eq!(lines.line(0), 0);
+        assert_eq!(lines.line(2), 1);
+        assert_eq!(lines.position(source, 6), Position { line: 2, column: 4 });
+        assert_eq!(
+            lines.position(source, source.len()),
+            Position { line: 3, column: 1 }
+        );
+    }
+}
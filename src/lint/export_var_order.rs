@@ -1,132 +1,445 @@
-//! TODO: Extend this to the full code order
-//! https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/gdscript_styleguide.html#code-order
+//! Checks that declarations follow the
+//! [code order](https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/gdscript_styleguide.html#code-order)
+//! of the style guide, at the top level and inside of inner classes.
 //!
-//! See [`Order`] for what's implemented already.
+//! See [`Order`] for the recognized declarations. Overridden custom methods can't be told apart
+//! from other methods, so they are ordered like any other method.
 
-use std::sync::Arc;
+use std::{ops::Range, sync::Arc};
 
-use miette::{LabeledSpan, Report, Severity};
-use owo_colors::OwoColorize;
+use miette::{LabeledSpan, Report};
 use tree_sitter::Node;
 
+use super::{
+    RuleContext,
+    fix::{Applicability, Edit, Fix, WithFix},
+};
 use crate::{NodeExt, query_struct::TopLevelDefinitionQuery};
 
+/// Virtual methods of the built-in classes that scripts commonly override.
+///
+/// The ones with their own slot in the code order are handled in [`definition_order`].
+const VIRTUAL_METHODS: &[&str] = &[
+    "_can_drop_data",
+    "_draw",
+    "_drop_data",
+    "_exit_tree",
+    "_get",
+    "_get_configuration_warnings",
+    "_get_drag_data",
+    "_get_minimum_size",
+    "_get_property_list",
+    "_gui_input",
+    "_has_point",
+    "_input",
+    "_input_event",
+    "_integrate_forces",
+    "_make_custom_tooltip",
+    "_mouse_enter",
+    "_mouse_exit",
+    "_notification",
+    "_property_can_revert",
+    "_property_get_revert",
+    "_run",
+    "_set",
+    "_shortcut_input",
+    "_to_string",
+    "_unhandled_input",
+    "_unhandled_key_input",
+    "_validate_property",
+];
+
 /// See also [`Order::to_numeric`].
 #[derive(Debug, Clone, Copy)]
 enum Order {
+    /// `@tool`, `@icon("res://icon.svg")` or `@static_unload`
+    ScriptAnnotation,
     /// `class_name Foo`
     ClassNameStatement,
     /// `extends Bar`
     ExtendsStatement,
     /// `## He who reads this is a poophead`
     DocComment,
-    /// `# He who reads this is cool`
-    Comment,
     /// `signal sharted(amount: float)`
     SignalStatement,
     /// `enum Foo { BAR, BAZ }`
     EnumDefinition,
     /// `const FOO := 2.0`
     ConstStatement,
+    /// `static var foo := 2.0`
+    StaticVariableStatement,
     /// `@export var foo := 2.0`
     ExportVariableStatement,
     /// `var foo := 2.0`
     VariableStatement,
     /// `@onready var foo = get_node("Foo")`
     OnReadyVariableStatement,
+    /// `static func _static_init() -> void`
+    StaticInit,
     /// `static func adopt_cat() -> void`
     StaticFunctionDefinition,
+    /// `func _init() -> void`
+    Init,
+    /// `func _enter_tree() -> void`
+    EnterTree,
+    /// `func _ready() -> void`
+    Ready,
+    /// `func _process(delta: float) -> void`
+    Process,
+    /// `func _physics_process(delta: float) -> void`
+    PhysicsProcess,
+    /// `func _input(event: InputEvent) -> void`, see [`VIRTUAL_METHODS`]
+    VirtualMethod,
     /// `func adopt_dog() -> void`
     FunctionDefinition,
+    /// `func _feed_dog() -> void`
+    PrivateFunctionDefinition,
+    /// `func _on_dog_barked() -> void`
+    SignalCallback,
+    /// `class Dog:`
+    ClassDefinition,
 }
 
 impl Order {
-    /// Returns an integer representing the relative order between top-level stuff.
+    /// Returns an integer representing the relative order between declarations.
     ///
     /// # Examples
     ///
     /// - `0200`: `class_name`
     /// - `1300`: remaining static methods
-    /// - `1302`: `_enter_tree`
+    /// - `1402`: `_enter_tree`
     ///
     /// See [code order](https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/gdscript_styleguide.html#code-order)
-    fn to_numeric(self) -> Option<usize> {
+    fn to_numeric(self) -> usize {
         match self {
-            Order::ClassNameStatement => Some(200),
-            Order::ExtendsStatement => Some(300),
-            Order::DocComment => Some(400),
-            Order::Comment => None,
-            Order::SignalStatement => Some(500),
-            Order::EnumDefinition => Some(600),
-            Order::ConstStatement => Some(700),
-            Order::ExportVariableStatement => Some(900),
-            Order::VariableStatement => Some(1000),
-            Order::OnReadyVariableStatement => Some(1100),
-            Order::StaticFunctionDefinition => Some(1300),
-            Order::FunctionDefinition => Some(1600),
+            Order::ScriptAnnotation => 100,
+            Order::ClassNameStatement => 200,
+            Order::ExtendsStatement => 300,
+            Order::DocComment => 400,
+            Order::SignalStatement => 500,
+            Order::EnumDefinition => 600,
+            Order::ConstStatement => 700,
+            Order::StaticVariableStatement => 800,
+            Order::ExportVariableStatement => 900,
+            Order::VariableStatement => 1000,
+            Order::OnReadyVariableStatement => 1100,
+            Order::StaticInit => 1200,
+            Order::StaticFunctionDefinition => 1300,
+            Order::Init => 1401,
+            Order::EnterTree => 1402,
+            Order::Ready => 1403,
+            Order::Process => 1404,
+            Order::PhysicsProcess => 1405,
+            Order::VirtualMethod => 1406,
+            Order::FunctionDefinition => 1600,
+            Order::PrivateFunctionDefinition => 1601,
+            Order::SignalCallback => 1602,
+            Order::ClassDefinition => 1700,
         }
     }
 }
 
+/// Annotations that apply to the whole script, all other annotations belong to the declaration below them.
+fn is_script_annotation(node: Node, source: &[u8]) -> bool {
+    node.kind() == "annotation"
+        && node
+            .child(1)
+            .is_some_and(|name| matches!(name.text(source), "tool" | "icon" | "static_unload"))
+}
+
+fn variable_order(definition: Node, source: &[u8]) -> Order {
+    if definition.child_by_field_name("static").is_some() {
+        return Order::StaticVariableStatement;
+    }
+
+    let mut cursor = definition.walk();
+    let annotations = definition
+        .children(&mut cursor)
+        .filter(|child| child.kind() == "annotations")
+        .flat_map(|annotations| {
+            annotations
+                .named_children(&mut annotations.walk())
+                .collect::<Vec<_>>()
+        })
+        .filter_map(|annotation| annotation.child(1))
+        .map(|name| name.text(source))
+        .collect::<Vec<_>>();
+    if annotations.iter().any(|name| name.starts_with("export")) {
+        Order::ExportVariableStatement
+    } else if annotations.contains(&"onready") {
+        Order::OnReadyVariableStatement
+    } else {
+        Order::VariableStatement
+    }
+}
+
+fn function_order(definition: Node, source: &[u8]) -> Option<Order> {
+    // `static` may follow annotations, e.g. `@rpc static func`
+    let mut cursor = definition.walk();
+    let is_static = definition
+        .children(&mut cursor)
+        .take_while(|child| child.kind() != "func")
+        .any(|child| child.kind() == "static_keyword");
+    let name = definition.child_by_field_name("name")?.text(source);
+    let order = match name {
+        "_static_init" if is_static => Order::StaticInit,
+        _ if is_static => Order::StaticFunctionDefinition,
+        "_enter_tree" => Order::EnterTree,
+        "_ready" => Order::Ready,
+        "_process" => Order::Process,
+        "_physics_process" => Order::PhysicsProcess,
+        _ if VIRTUAL_METHODS.contains(&name) => Order::VirtualMethod,
+        _ if name.starts_with("_on_") => Order::SignalCallback,
+        _ if name.starts_with('_') => Order::PrivateFunctionDefinition,
+        _ => Order::FunctionDefinition,
+    };
+    Some(order)
+}
+
 fn definition_order(definition: Node, source: &[u8]) -> Option<Order> {
     match definition.kind() {
+        "annotation" if is_script_annotation(definition, source) => Some(Order::ScriptAnnotation),
         "class_name_statement" => Some(Order::ClassNameStatement),
         "extends_statement" => Some(Order::ExtendsStatement),
-        "comment" => match definition.text(source).starts_with("##") {
-            true => Some(Order::DocComment),
-            false => Some(Order::Comment),
-        },
         "signal_statement" => Some(Order::SignalStatement),
         "enum_definition" => Some(Order::EnumDefinition),
         "const_statement" => Some(Order::ConstStatement),
-        "variable_statement" => match definition
-            .child_by_field_name("annotation")
-            .map(|annotation| annotation.text(source))
-        {
-            None => Some(Order::VariableStatement),
-            Some("export") => Some(Order::ExportVariableStatement),
-            Some("onready") => Some(Order::OnReadyVariableStatement),
-            _ => None,
-        },
-        // TODO: There's a lot left to handle for methods
-        "function_definition" => match definition.child(0).expect("at least one child").kind() {
-            "static_keyword" => Some(Order::StaticFunctionDefinition),
-            _ => Some(Order::FunctionDefinition),
-        },
+        "variable_statement" => Some(variable_order(definition, source)),
+        "constructor_definition" => Some(Order::Init),
+        "function_definition" => function_order(definition, source),
+        "class_definition" => Some(Order::ClassDefinition),
         _ => None,
     }
 }
 
-pub fn check_export_var_order(root: Node, source: Arc<str>) -> Vec<Report> {
+/// A declaration together with the comments and annotations that move with it.
+#[derive(Debug)]
+struct Entry<'tree> {
+    node: Node<'tree>,
+    /// Covers the leading comments and annotations, the declaration and its trailing comment.
+    range: Range<usize>,
+    order: Option<Order>,
+}
+
+fn is_header(node: Node) -> bool {
+    matches!(
+        node.kind(),
+        "annotation" | "class_name_statement" | "extends_statement"
+    )
+}
+
+fn is_definition(node: Node) -> bool {
+    matches!(
+        node.kind(),
+        "function_definition" | "constructor_definition" | "class_definition"
+    )
+}
+
+/// Groups the nodes of a script or class body into entries.
+///
+/// Comments and annotations like `@warning_ignore` belong to the declaration below them,
+/// except for trailing comments on the same line, indented comments (which belong to the body
+/// above them) and the `##` class documentation right below the header.
+fn entries<'tree>(nodes: &[Node<'tree>], source: &[u8]) -> Vec<Entry<'tree>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut leading: Option<usize> = None;
+    let mut class_doc: Option<Entry> = None;
+
+    for &node in nodes {
+        if node.kind() == "annotation" && !is_script_annotation(node, source) {
+            entries.extend(class_doc.take());
+            leading.get_or_insert(node.start_byte());
+            continue;
+        }
+        if node.kind() != "comment" {
+            if let Some(doc) = class_doc.take() {
+                entries.push(doc);
+            }
+            entries.push(Entry {
+                node,
+                range: leading.take().unwrap_or(node.start_byte())..node.end_byte(),
+                order: definition_order(node, source),
+            });
+            continue;
+        }
+
+        let prev = entries
+            .last_mut()
+            .filter(|_| class_doc.is_none() && leading.is_none());
+        let is_trailing = prev.as_ref().is_some_and(|prev| {
+            prev.node.end_position().row == node.start_position().row
+                || node.start_position().column > prev.node.start_position().column
+        });
+        if let Some(prev) = prev.filter(|_| is_trailing) {
+            prev.range.end = node.end_byte();
+            continue;
+        }
+
+        let is_class_doc = node.text(source).starts_with("##")
+            && leading.is_none()
+            && !entries.is_empty()
+            && entries.iter().all(|entry| is_header(entry.node));
+        match (&mut class_doc, is_class_doc) {
+            (Some(doc), _) if doc.node.end_position().row + 1 == node.start_position().row => {
+                doc.node = node;
+                doc.range.end = node.end_byte();
+            }
+            (None, true) => {
+                class_doc = Some(Entry {
+                    node,
+                    range: node.byte_range(),
+                    order: Some(Order::DocComment),
+                })
+            }
+            _ => {
+                if let Some(doc) = class_doc.take() {
+                    entries.push(doc);
+                }
+                leading.get_or_insert(node.start_byte());
+            }
+        }
+    }
+    entries.extend(class_doc);
+    entries
+}
+
+/// Reorders the entries into code order, keeping the relative order of equal entries.
+///
+/// Entries without an order stay behind the entry they follow.
+fn reorder_fix(entries: &[Entry], source: &str) -> Option<Fix> {
+    let (first, last) = (entries.first()?, entries.last()?);
+
+    // Anything but whitespace between the entries would get lost
+    let gaps_are_blank = entries.windows(2).all(|pair| {
+        source[pair[0].range.end..pair[1].range.start]
+            .trim()
+            .is_empty()
+    });
+    if !gaps_are_blank {
+        return None;
+    }
+
+    let mut current = 0;
+    let mut keys = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            if let Some(order) = entry.order.map(Order::to_numeric) {
+                current = order;
+            }
+            (current, index)
+        })
+        .collect::<Vec<_>>();
+    keys.sort();
+    if keys
+        .iter()
+        .enumerate()
+        .all(|(index, (_, original))| index == *original)
+    {
+        return None;
+    }
+
+    // Inner classes are indented, moved entries have to be as well
+    let line_start = source[..first.range.start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &source[line_start..first.range.start];
+
+    let mut replacement = String::new();
+    for (position, &(order, index)) in keys.iter().enumerate() {
+        let entry = &entries[index];
+        if let Some(&(prev_order, prev_index)) = position.checked_sub(1).map(|p| &keys[p]) {
+            let prev = &entries[prev_index];
+            if prev_index + 1 == index {
+                replacement.push_str(&source[prev.range.end..entry.range.start]);
+            } else {
+                // The header up to the class documentation stays together
+                let is_header = order <= Order::DocComment.to_numeric();
+                let blank_lines = match (
+                    prev_order == order || is_header,
+                    is_definition(prev.node) || is_definition(entry.node),
+                ) {
+                    (_, true) => 2,
+                    (true, false) => 0,
+                    (false, false) => 1,
+                };
+                replacement.push_str(&"\n".repeat(blank_lines + 1));
+                replacement.push_str(indent);
+            }
+        }
+        replacement.push_str(&source[entry.range.clone()]);
+    }
+
+    // Moving initializers past each other can change what they evaluate to
+    let has_value = |entry: &&Entry| {
+        matches!(entry.node.kind(), "variable_statement" | "const_statement")
+            && entry.node.child_by_field_name("value").is_some()
+    };
+    let original = entries
+        .iter()
+        .filter(has_value)
+        .map(|entry| entry.node.id());
+    let reordered = keys
+        .iter()
+        .map(|&(_, index)| &entries[index])
+        .filter(has_value)
+        .map(|entry| entry.node.id());
+    let applicability = match original.eq(reordered) {
+        true => Applicability::Safe,
+        false => Applicability::Unsafe,
+    };
+
+    Some(Fix::new(
+        applicability,
+        vec![Edit::replace(
+            first.range.start..last.range.end,
+            replacement,
+        )],
+    ))
+}
+
+pub fn check_export_var_order(root: Node, source: Arc<str>, ctx: &RuleContext) -> Vec<Report> {
     assert!(root.kind() == "source", "Expected 'source' node");
 
     let mut reports = Vec::new();
-    let declarations = TopLevelDefinitionQuery::query(root, source.as_bytes())
+    check_block(root, &source, ctx, &mut reports);
+    reports
+}
+
+/// Checks the declarations of the script or of a class body, and of the inner classes in it.
+fn check_block(block: Node, source: &Arc<str>, ctx: &RuleContext, reports: &mut Vec<Report>) {
+    let nodes = TopLevelDefinitionQuery::query(block, source.as_bytes())
         .into_iter()
-        .filter_map(|result| {
+        .map(|result| result.definition)
+        // A `pass` in an otherwise empty class isn't a declaration
+        .filter(|node| node.kind() != "pass_statement")
+        .collect::<Vec<_>>();
+    let entries = entries(&nodes, source.as_bytes());
+
+    let declarations = entries
+        .iter()
+        .filter_map(|entry| {
             // If we can't recognize the thing, warn the user.
-            let Some(order) = definition_order(result.definition, source.as_bytes()) else {
+            let Some(order) = entry.order else {
                 reports.push(
                     miette::miette!(
-                        severity = Severity::Warning,
+                        severity = ctx.severity,
                         code = "top-level-order-unknown",
                         labels = vec![LabeledSpan::new_with_span(
-                            Some(result.definition.kind().to_string()),
-                            result.definition.to_source_point_start(),
+                            Some(entry.node.kind().to_string()),
+                            entry.node.to_source_point_start(),
                         )],
                         url = "https://github.com/cryeprecision/gdscript-foli/issues",
                         help = "gotta complain to the idiot developer about this one",
                         "statement has no associated order"
                     )
-                    .with_source_code(Arc::clone(&source)),
+                    .with_source_code(Arc::clone(source)),
                 );
                 return None;
             };
-            // If we recognized the thing but didn't associate an order, ignore it.
-            order.to_numeric().map(|num| (result, num))
+            Some((entry.node, order.to_numeric()))
         })
         .collect::<Vec<_>>();
 
+    let fix = reorder_fix(&entries, source);
     for i in 0..declarations.len() {
         let (declaration_i, order_i) = &declarations[i];
 
@@ -142,34 +455,284 @@ pub fn check_export_var_order(root: Node, source: Arc<str>) -> Vec<Report> {
 
         if let Some((declaration_j, order_j)) = out_of_order {
             // TODO: I feel like this error message is kind of hard to read
-            reports.push(
-                miette::miette!(
-                    severity = Severity::Warning,
-                    code = "top-level-order",
-                    labels = vec![
-                        LabeledSpan::new_with_span(
-                            Some(format!(
-                                "{} ({}) should come {}",
-                                declaration_j.definition.kind(),
-                                order_j,
-                                "after".underline()
-                            )),
-                            declaration_j.definition.to_source_point_start(),
-                        ),
-                        LabeledSpan::new_primary_with_span(
-                            Some(format!("{} ({})", declaration_i.definition.kind(), order_i)),
-                            declaration_i.definition.to_source_point_start(),
-                        )
-                    ],
-                    url = "https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/\
-                                gdscript_styleguide.html#code-order",
-                    help = "move the bottom one above the top one to fix this",
-                    "invalid declaration order (see link)",
-                )
-                .with_source_code(Arc::clone(&source)),
-            );
+            let report = miette::miette!(
+                severity = ctx.severity,
+                code = "top-level-order",
+                labels = vec![
+                    LabeledSpan::new_with_span(
+                        Some(format!(
+                            "{} ({}) should come after",
+                            declaration_j.kind(),
+                            order_j,
+                        )),
+                        declaration_j.to_source_point_start(),
+                    ),
+                    LabeledSpan::new_primary_with_span(
+                        Some(format!("{} ({})", declaration_i.kind(), order_i)),
+                        declaration_i.to_source_point_start(),
+                    )
+                ],
+                url = "https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/\
+                            gdscript_styleguide.html#code-order",
+                help = "move the bottom one above the top one to fix this",
+                "invalid declaration order (see link)",
+            )
+            .with_source_code(Arc::clone(source));
+
+            // The fix reorders the whole block, it's attached to every report so it survives the
+            // suppression of some of them. Applying the fixes skips the identical copies.
+            reports.push(match &fix {
+                Some(fix) => report.with_fix(fix.clone()),
+                None => report,
+            });
         }
     }
 
-    reports
+    for entry in &entries {
+        if let Some(body) = entry
+            .node
+            .child_by_field_name("body")
+            .filter(|_| entry.node.kind() == "class_definition")
+        {
+            check_block(body, source, ctx, reports);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn check(source: &str) -> Vec<Report> {
+        let mut parser = tree_sitter::Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let ctx = RuleContext::new(&crate::lint::RULES[0], None, Path::new("test.gd")).unwrap();
+        check_export_var_order(tree.root_node(), source.into(), &ctx)
+    }
+
+    /// Applies the fixes of the rule, returns `None` if there are none.
+    fn reorder(source: &str) -> Option<(String, Applicability)> {
+        let reports = check(source);
+        let fixes = reports
+            .iter()
+            .filter_map(crate::lint::fix::fix_of)
+            .collect::<Vec<_>>();
+        let applicability = fixes.iter().map(|fix| fix.applicability).max()?;
+        let (output, _) = crate::lint::fix::apply_fixes(source, fixes);
+        Some((output, applicability))
+    }
+
+    #[test]
+    fn reorders_with_comments() {
+        let source = "\
+extends Node
+## Class docs.
+
+# About f
+func f():
+	pass
+
+
+var x # trailing
+## Docs for s.
+signal s
+signal t
+";
+        let expected = "\
+extends Node
+## Class docs.
+
+## Docs for s.
+signal s
+signal t
+
+var x # trailing
+
+
+# About f
+func f():
+	pass
+";
+        assert_eq!(
+            reorder(source),
+            Some((expected.to_string(), Applicability::Safe))
+        );
+        assert_eq!(reorder(expected), None);
+    }
+
+    #[test]
+    fn suppressed_reports_keep_the_fix() {
+        let source = "\
+func f():
+\tpass
+# foli-ignore: top-level-order
+signal s
+var x
+";
+        let expected = "\
+# foli-ignore: top-level-order
+signal s
+var x
+
+
+func f():
+\tpass
+";
+        let tree = crate::new_parser().unwrap().parse(source, None).unwrap();
+        let rules = crate::config::RuleConfigs::default();
+        let reports = crate::run_checks(tree.root_node(), source.into(), &rules, "a.gd".as_ref());
+        let fixes = reports
+            .iter()
+            .filter(|report| report.code().unwrap().to_string() == "top-level-order")
+            .map(|report| crate::lint::fix::fix_of(report).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(
+            crate::lint::fix::apply_fixes(source, fixes),
+            (expected.to_string(), 1)
+        );
+        // Applying the same fix of every report reorders once
+        assert_eq!(
+            crate::lint::fix::apply_fixes(
+                source,
+                check(source).iter().filter_map(crate::lint::fix::fix_of)
+            ),
+            (expected.to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn reordering_initializers_is_unsafe() {
+        let (output, applicability) = reorder("var a = 1\nconst B = 2\n").unwrap();
+        assert_eq!(output, "const B = 2\n\nvar a = 1\n");
+        assert_eq!(applicability, Applicability::Unsafe);
+    }
+
+    #[test]
+    fn full_code_order() {
+        let source = "\
+@tool
+class_name Dog
+extends Node
+## Docs.
+
+signal barked
+enum Mood { HAPPY }
+const LEGS = 4
+static var count = 0
+@export_range(0, 10) var age = 1
+var name = \"Rex\"
+@onready var tail = $Tail
+
+
+static func _static_init():
+	pass
+
+
+static func adopt():
+	pass
+
+
+func _init():
+	pass
+
+
+func _enter_tree():
+	pass
+
+
+func _ready():
+	pass
+
+
+func _process(delta):
+	pass
+
+
+func _physics_process(delta):
+	pass
+
+
+func _input(event):
+	pass
+
+
+func bark():
+	pass
+
+
+@warning_ignore(\"unused\")
+func _wag():
+	pass
+
+
+func _on_ball_thrown():
+	pass
+
+
+class Puppy:
+	pass
+";
+        let codes = check(source)
+            .iter()
+            .map(|report| report.code().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, Vec::<String>::new());
+    }
+
+    #[test]
+    fn reorders_inner_classes() {
+        let source = "\
+class Inner:
+	func f():
+		pass
+
+	signal s
+";
+        let expected = "\
+class Inner:
+	signal s
+
+
+	func f():
+		pass
+";
+        assert_eq!(
+            reorder(source),
+            Some((expected.to_string(), Applicability::Safe))
+        );
+    }
+
+    /// The grammar only accepts annotations after `static` for now, Godot also accepts
+    /// `@rpc static func`.
+    #[test]
+    fn static_functions_with_annotations() {
+        let source = "\
+func f():
+	pass
+
+
+static @rpc func g():
+	pass
+";
+        let expected = "\
+static @rpc func g():
+	pass
+
+
+func f():
+	pass
+";
+        assert_eq!(
+            reorder(source),
+            Some((expected.to_string(), Applicability::Safe))
+        );
+        // Annotations alone don't make a function static
+        assert!(reorder("@rpc func f():\n\tpass\n\n\nstatic func g():\n\tpass\n").is_some());
+    }
 }
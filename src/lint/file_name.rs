@@ -0,0 +1,113 @@
+//! Checks that script files are named like the class they declare, e.g. `player_controller.gd`
+//! for `class_name PlayerController`, and that file names follow a consistent case.
+
+use std::sync::Arc;
+
+use miette::{LabeledSpan, Report};
+use serde::Deserialize;
+use tree_sitter::Node;
+
+use super::{RuleContext, naming_convention::Case};
+use crate::{NodeExt, query_struct::TopLevelDefinitionQuery};
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Options {
+    /// The case of the file names, `snake` as recommended by the style guide or `pascal` like
+    /// the class names.
+    pub case: Case,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { case: Case::Snake }
+    }
+}
+
+pub fn check_file_name(root: Node, source: Arc<str>, ctx: &RuleContext) -> Vec<Report> {
+    assert!(root.kind() == "source", "Expected 'source' node");
+
+    let options: Options = ctx.options();
+    let (Some(stem), Some(file_name)) = (
+        ctx.path.file_stem().and_then(|stem| stem.to_str()),
+        ctx.path.file_name().and_then(|name| name.to_str()),
+    ) else {
+        return Vec::new();
+    };
+    let extension = &file_name[stem.len()..];
+
+    let class_name = TopLevelDefinitionQuery::query(root, source.as_bytes())
+        .into_iter()
+        .map(|result| result.definition)
+        .find(|definition| definition.kind() == "class_name_statement")
+        .and_then(|statement| statement.named_child(0));
+
+    if let Some(class_name) = class_name {
+        let expected = options.case.convert(class_name.text(source.as_bytes()));
+        if stem != expected {
+            return vec![
+                miette::miette!(
+                    severity = ctx.severity,
+                    code = "file-name-mismatch",
+                    labels = vec![LabeledSpan::new_primary_with_span(
+                        Some(format!("declared in `{file_name}`")),
+                        class_name.to_source_span(),
+                    )],
+                    help = format!("rename the file to `{expected}{extension}`"),
+                    "the file name doesn't match the class name",
+                )
+                .with_source_code(Arc::clone(&source)),
+            ];
+        }
+    }
+
+    if !options.case.is_match(stem) {
+        return vec![
+            miette::miette!(
+                severity = ctx.severity,
+                code = "file-name",
+                help = format!(
+                    "rename the file to `{}{extension}`",
+                    options.case.convert(stem)
+                ),
+                "file name `{file_name}` should be {}",
+                options.case.describe(),
+            )
+            .with_source_code(Arc::clone(&source)),
+        ];
+    }
+
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::config::RuleConfig;
+
+    fn codes(source: &str, path: &str, options: &str) -> Vec<String> {
+        let mut parser = tree_sitter::Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let config: RuleConfig = toml::from_str(options).unwrap();
+        let rule = crate::lint::rule_for_code("file-name").unwrap();
+        let ctx = RuleContext::new(rule, Some(&config), Path::new(path)).unwrap();
+        check_file_name(tree.root_node(), source.into(), &ctx)
+            .iter()
+            .map(|report| report.code().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn file_names() {
+        let source = "class_name PlayerController\n";
+        assert!(codes(source, "src/player_controller.gd", "").is_empty());
+        assert_eq!(codes(source, "src/player.gd", ""), ["file-name-mismatch"]);
+        assert_eq!(codes("", "src/Player.gd", ""), ["file-name"]);
+        assert!(codes(source, "PlayerController.gd", "case = \"pascal\"").is_empty());
+    }
+}
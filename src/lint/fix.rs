@@ -0,0 +1,194 @@
+//! Machine applicable fixes that rules attach to their diagnostics.
+//!
+//! Rules keep creating their reports with [`miette::miette!`] and attach a [`Fix`] with
+//! [`WithFix::with_fix`], the fix is retrieved again with [`fix_of`].
+
+use std::{fmt, ops::Range};
+
+use miette::{Diagnostic, Report};
+use serde::{Deserialize, Serialize};
+
+/// Whether a fix can be applied without looking at it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Applicability {
+    /// Keeps the behavior of the code, applied with `--fix`.
+    Safe,
+    /// May change the behavior of the code, only applied with `--fix-unsafe`.
+    Unsafe,
+}
+
+/// Replaces a byte range of the source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl Edit {
+    pub fn replace(range: Range<usize>, replacement: impl Into<String>) -> Self {
+        Self {
+            range,
+            replacement: replacement.into(),
+        }
+    }
+}
+
+/// A set of edits that are applied together, or not at all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fix {
+    pub applicability: Applicability,
+    pub edits: Vec<Edit>,
+}
+
+impl Fix {
+    pub fn new(applicability: Applicability, edits: Vec<Edit>) -> Self {
+        Self {
+            applicability,
+            edits,
+        }
+    }
+
+    /// The smallest range covering all edits.
+    fn range(&self) -> Range<usize> {
+        let start = self.edits.iter().map(|edit| edit.range.start).min();
+        let end = self.edits.iter().map(|edit| edit.range.end).max();
+        start.unwrap_or(0)..end.unwrap_or(0)
+    }
+}
+
+/// A report with a fix attached, behaves exactly like the wrapped report otherwise.
+struct Fixable {
+    report: Report,
+    fix: Fix,
+}
+
+impl fmt::Debug for Fixable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.report, f)
+    }
+}
+
+impl fmt::Display for Fixable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.report, f)
+    }
+}
+
+impl std::error::Error for Fixable {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.report.source()
+    }
+}
+
+impl Diagnostic for Fixable {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.report.code()
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        self.report.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.report.help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.report.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        self.report.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        self.report.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.report.related()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        self.report.diagnostic_source()
+    }
+}
+
+pub trait WithFix {
+    fn with_fix(self, fix: Fix) -> Report;
+}
+
+impl WithFix for Report {
+    fn with_fix(self, fix: Fix) -> Report {
+        Report::new(Fixable { report: self, fix })
+    }
+}
+
+/// The fix attached to a report, if any.
+pub fn fix_of(report: &Report) -> Option<&Fix> {
+    report.downcast_ref::<Fixable>().map(|fixable| &fixable.fix)
+}
+
+/// Applies the fixes that don't overlap with each other, earlier fixes win.
+///
+/// Returns the new source and the number of applied fixes.
+pub fn apply_fixes<'a>(source: &str, fixes: impl IntoIterator<Item = &'a Fix>) -> (String, usize) {
+    let mut fixes = fixes.into_iter().collect::<Vec<_>>();
+    fixes.sort_by_key(|fix| (fix.range().start, fix.range().end));
+
+    let mut edits: Vec<&Edit> = Vec::new();
+    let mut applied = 0;
+    for fix in fixes {
+        let overlaps = fix.edits.iter().any(|edit| {
+            edits.iter().any(|other| {
+                edit.range.start < other.range.end && other.range.start < edit.range.end
+                    // Two insertions at the same offset would be ambiguous
+                    || edit.range == other.range
+            })
+        });
+        if overlaps || fix.edits.is_empty() {
+            continue;
+        }
+        edits.extend(&fix.edits);
+        applied += 1;
+    }
+
+    // Back to front, so the offsets of the remaining edits stay valid
+    edits.sort_by_key(|edit| std::cmp::Reverse((edit.range.start, edit.range.end)));
+    let mut output = source.to_string();
+    for edit in edits {
+        output.replace_range(edit.range.clone(), &edit.replacement);
+    }
+    (output, applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixes_survive_the_report() {
+        let report = miette::miette!(code = "some-rule", "message")
+            .with_fix(Fix::new(Applicability::Safe, vec![Edit::replace(0..1, "")]));
+        assert_eq!(report.code().unwrap().to_string(), "some-rule");
+        assert_eq!(
+            fix_of(&report).unwrap().edits,
+            vec![Edit::replace(0..1, "")]
+        );
+        assert!(fix_of(&miette::miette!("no fix")).is_none());
+    }
+
+    #[test]
+    fn overlapping_fixes_are_skipped() {
+        let safe = |edits| Fix::new(Applicability::Safe, edits);
+        let fixes = [
+            safe(vec![Edit::replace(4..7, "bar"), Edit::replace(0..0, "# ")]),
+            safe(vec![Edit::replace(5..6, "x")]),
+            safe(vec![Edit::replace(8..9, "")]),
+        ];
+        let (output, applied) = apply_fixes("var foo = 1", &fixes);
+        assert_eq!(output, "# var bar  1");
+        assert_eq!(applied, 2);
+    }
+}
@@ -0,0 +1,132 @@
+//! Keep individual lines of code under 100 characters.
+//!
+//! https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/gdscript_styleguide.html#line-length
+
+use std::sync::Arc;
+
+use miette::{LabeledSpan, Report};
+use serde::Deserialize;
+use tree_sitter::Node;
+
+use super::RuleContext;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Options {
+    /// The maximum number of columns a line may take up.
+    pub max_length: usize,
+    /// How many columns a tab character advances to.
+    pub tab_width: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_length: 100,
+            tab_width: 4,
+        }
+    }
+}
+
+pub fn check_line_length(root: Node, source: Arc<str>, ctx: &RuleContext) -> Vec<Report> {
+    assert!(root.kind() == "source", "Expected 'source' node");
+
+    let options: Options = ctx.options();
+    let tab_width = options.tab_width.max(1);
+    let mut reports = Vec::new();
+
+    let mut line_start = 0;
+    for line in source.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+
+        let mut width = 0;
+        let mut overflow = None;
+        for (offset, ch) in content.char_indices() {
+            width += match ch {
+                '\t' => tab_width - width % tab_width,
+                _ => 1,
+            };
+            if width > options.max_length && overflow.is_none() {
+                overflow = Some(offset);
+            }
+        }
+
+        if let Some(overflow) = overflow {
+            reports.push(
+                miette::miette!(
+                    severity = ctx.severity,
+                    code = "line-length",
+                    labels = vec![LabeledSpan::new(
+                        Some(format!("{width} > {}", options.max_length)),
+                        line_start + overflow,
+                        content.len() - overflow,
+                    )],
+                    url = "https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/\
+                                gdscript_styleguide.html#line-length",
+                    help = "split the line, expressions in parentheses may span multiple lines",
+                    "line is longer than {} columns",
+                    options.max_length,
+                )
+                .with_source_code(Arc::clone(&source)),
+            );
+        }
+
+        line_start += line.len();
+    }
+
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::config::RuleConfig;
+
+    /// The offsets and lengths of the reported parts of the lines.
+    fn overflows(source: &str, options: &str) -> Vec<(usize, usize)> {
+        let tree = crate::new_parser().unwrap().parse(source, None).unwrap();
+        let config: RuleConfig = toml::from_str(options).unwrap();
+        let rule = crate::lint::rule_for_code("line-length").unwrap();
+        let ctx = RuleContext::new(rule, Some(&config), Path::new("a.gd")).unwrap();
+        check_line_length(tree.root_node(), source.into(), &ctx)
+            .iter()
+            .map(|report| {
+                let label = report.labels().unwrap().next().unwrap();
+                (label.offset(), label.len())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn long_lines() {
+        let long = format!("var a = \"{}\"\n", "x".repeat(91));
+        assert_eq!(overflows(&long, ""), [(100, 1)]);
+        assert!(overflows(&long, "max-length = 101").is_empty());
+        // Line endings don't count
+        assert!(overflows(&long.replace('\n', "\r\n"), "max-length = 101").is_empty());
+        // The overflow starts at the first character past the limit, on every line
+        assert_eq!(
+            overflows("var a = 1\nvar bb = 2\n", "max-length = 9"),
+            [(19, 1)]
+        );
+    }
+
+    #[test]
+    fn tabs() {
+        let source = "func f():\n\t\tvar a = 1\n";
+        // Two tabs take up 8 columns, the line is 17 columns wide (13 with a tab width of 2)
+        assert!(overflows(source, "max-length = 17").is_empty());
+        assert_eq!(overflows(source, "max-length = 16"), [(20, 1)]);
+        assert_eq!(
+            overflows(source, "max-length = 9\ntab-width = 2"),
+            [(17, 4)]
+        );
+        // A tab advances to the next tab stop
+        assert_eq!(
+            overflows("\t\ta\n", "max-length = 5\ntab-width = 4"),
+            [(1, 2)]
+        );
+    }
+}
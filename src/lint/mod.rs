@@ -1,4 +1,15 @@
-pub type CheckFn = fn(root: tree_sitter::Node, source: std::sync::Arc<str>) -> Vec<miette::Report>;
+use std::path::Path;
+
+use miette::Severity;
+use serde::{Deserialize, de::DeserializeOwned};
+
+use crate::config::RuleConfig;
+
+pub type CheckFn = fn(
+    root: tree_sitter::Node,
+    source: std::sync::Arc<str>,
+    ctx: &RuleContext,
+) -> Vec<miette::Report>;
 
 mod export_var_order;
 pub use export_var_order::check_export_var_order;
@@ -11,3 +22,156 @@ pub use no_print_call::check_no_print_call;
 
 mod naming_convention;
 pub use naming_convention::check_naming_convention;
+
+mod line_length;
+pub use line_length::check_line_length;
+
+mod file_name;
+pub use file_name::check_file_name;
+
+mod suppression;
+pub use suppression::Suppressions;
+
+pub mod fix;
+
+pub mod syntax;
+
+/// A lint rule together with its defaults.
+pub struct Rule {
+    /// The diagnostic code of the rule, also used as its key in `foli.toml`.
+    pub code: &'static str,
+    /// What the rule checks, in a few words.
+    pub description: &'static str,
+    pub check: CheckFn,
+    pub severity: Severity,
+    /// Checks the rule specific options from `foli.toml`.
+    pub validate: fn(&toml::Table) -> Result<(), toml::de::Error>,
+}
+
+pub static RULES: &[Rule] = &[
+    Rule {
+        code: "top-level-order",
+        description: "Declarations follow the code order of the style guide",
+        check: check_export_var_order,
+        severity: Severity::Warning,
+        validate: validate_options::<NoOptions>,
+    },
+    Rule {
+        code: "typed-function-signature",
+        description: "Function parameters and return values have type hints",
+        check: check_typed_function_signature,
+        severity: Severity::Warning,
+        validate: validate_options::<typed_function_signature::Options>,
+    },
+    Rule {
+        code: "no-print",
+        description: "No print calls, a logger is used instead",
+        check: check_no_print_call,
+        severity: Severity::Warning,
+        validate: validate_options::<no_print_call::Options>,
+    },
+    Rule {
+        code: "naming-convention",
+        description: "Names follow the naming conventions of the style guide",
+        check: check_naming_convention,
+        severity: Severity::Warning,
+        validate: validate_options::<naming_convention::Options>,
+    },
+    Rule {
+        code: "line-length",
+        description: "Lines aren't longer than the maximum length",
+        check: check_line_length,
+        severity: Severity::Warning,
+        validate: validate_options::<line_length::Options>,
+    },
+    Rule {
+        code: "file-name",
+        description: "File names match the declared class name",
+        check: check_file_name,
+        severity: Severity::Warning,
+        validate: validate_options::<file_name::Options>,
+    },
+];
+
+/// Reported by [`Suppressions::unused`] instead of a check function, but configured like any other rule.
+pub static UNUSED_SUPPRESSION: Rule = Rule {
+    code: "unused-suppression",
+    description: "Suppression comments suppress a diagnostic",
+    check: |_, _, _| Vec::new(),
+    severity: Severity::Warning,
+    validate: validate_options::<NoOptions>,
+};
+
+/// Finds the rule that emits diagnostics with the given code.
+///
+/// Rules may emit more specific codes, e.g. `top-level-order-unknown` is emitted by `top-level-order`.
+pub fn rule_for_code(code: &str) -> Option<&'static Rule> {
+    RULES
+        .iter()
+        .chain(std::iter::once(&UNUSED_SUPPRESSION))
+        .find(|rule| {
+            code.strip_prefix(rule.code)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+        })
+}
+
+/// What diagnostics with the given code are about, also works for syntax errors.
+pub fn describe_code(code: &str) -> Option<&'static str> {
+    if code == syntax::CODE {
+        return Some(syntax::DESCRIPTION);
+    }
+    rule_for_code(code).map(|rule| rule.description)
+}
+
+/// The settings a rule is run with, resolved from the defaults and `foli.toml`.
+pub struct RuleContext<'a> {
+    pub severity: Severity,
+    /// The file that is checked.
+    pub path: &'a Path,
+    options: &'a toml::Table,
+}
+
+impl<'a> RuleContext<'a> {
+    /// Returns `None` if the rule is disabled.
+    pub fn new(rule: &Rule, config: Option<&'a RuleConfig>, path: &'a Path) -> Option<Self> {
+        static NO_OPTIONS: std::sync::LazyLock<toml::Table> =
+            std::sync::LazyLock::new(toml::Table::new);
+
+        if config.and_then(|config| config.enabled) == Some(false) {
+            return None;
+        }
+        Some(Self {
+            severity: config
+                .and_then(|config| config.severity)
+                .map(Severity::from)
+                .unwrap_or(rule.severity),
+            path,
+            options: config.map(|config| &config.options).unwrap_or(&NO_OPTIONS),
+        })
+    }
+
+    /// Parses the rule specific options.
+    pub fn options<T: DeserializeOwned>(&self) -> T {
+        parse_options(self.options).expect("options are validated when loading the config")
+    }
+}
+
+/// For rules that don't have any options.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoOptions {}
+
+fn parse_options<T: DeserializeOwned>(options: &toml::Table) -> Result<T, toml::de::Error> {
+    options.clone().try_into()
+}
+
+fn validate_options<T: DeserializeOwned>(options: &toml::Table) -> Result<(), toml::de::Error> {
+    parse_options::<T>(options).map(|_| ())
+}
+
+pub fn validate_rule_config(code: &str, config: &RuleConfig) -> anyhow::Result<()> {
+    let Some(rule) = rule_for_code(code).filter(|rule| rule.code == code) else {
+        anyhow::bail!("unknown rule {code:?}");
+    };
+    (rule.validate)(&config.options).map_err(|err| anyhow::anyhow!("rule {code:?}: {err}"))
+}
@@ -1,22 +1,543 @@
-//! - [ ] Function name
-//! - [ ] Class name
-//! - [ ] Sub-class name
-//! - [ ] Signal name
-//! - [ ] Class variable name
+//! Checks names against the [naming conventions](https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/gdscript_styleguide.html#naming-conventions)
+//! of the style guide, every category can be overridden with a regex in `foli.toml`.
+//!
+//! - [x] Function name
+//! - [x] Class name
+//! - [x] Sub-class name
+//! - [x] Signal name
+//! - [x] Class variable name
 //! - [ ] Class load variable name
-//! - [ ] Function variable name
+//! - [x] Function variable name
 //! - [ ] Function preload variable name
-//! - [ ] Function argument name
-//! - [ ] Loop variable name
-//! - [ ] Enum name
-//! - [ ] Constant name
-//! - [ ] Load constant name
+//! - [x] Function argument name
+//! - [x] Loop variable name
+//! - [x] Enum name
+//! - [x] Enum member name
+//! - [x] Constant name
+//! - [x] Load constant name
+//!
+//! Names that break the default conventions can be renamed with `--fix-unsafe`, the fix renames
+//! every identifier with the same name in the file. Other scripts and scenes aren't updated.
 
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    sync::{Arc, LazyLock},
+};
 
-use miette::Report;
+use miette::{LabeledSpan, Report};
+use regex::Regex;
+use serde::Deserialize;
 use tree_sitter::Node;
 
-pub fn check_naming_convention(root: Node, source: Arc<str>) -> Vec<Report> {
-    vec![]
+use super::{
+    RuleContext,
+    fix::{Applicability, Edit, Fix, WithFix},
+};
+use crate::{
+    NodeExt,
+    query_struct::{IdentifierQuery, NamingQuery},
+};
+
+/// `snake_case`, optionally with a single leading underscore for private members.
+///
+/// A lone `_` is allowed as well, it's the convention for unused variables.
+static SNAKE_CASE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("^(_|_?[a-z][a-z0-9]*(_[a-z0-9]+)*)$").expect("valid regex"));
+
+/// `PascalCase`, acronyms like in `HTTPRequest` are fine.
+static PASCAL_CASE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("^_?[A-Z][A-Za-z0-9]*$").expect("valid regex"));
+
+/// `CONSTANT_CASE`, optionally with a single leading underscore for private constants.
+static CONSTANT_CASE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("^_?[A-Z][A-Z0-9]*(_[A-Z0-9]+)*$").expect("valid regex"));
+
+/// Resources that are loaded into a constant and used like a class, e.g. `Bullet.instantiate()`.
+const CLASS_LIKE_EXTENSIONS: &[&str] = &[".gd", ".tscn", ".scn"];
+
+/// A regex that has to match the whole name.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Pattern {
+    regex: Regex,
+    /// As written in `foli.toml`, without the anchors.
+    pattern: String,
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = regex::Error;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        let regex = Regex::new(&format!("^(?:{pattern})$"))?;
+        Ok(Self { regex, pattern })
+    }
+}
+
+/// Overrides the expected form of the names per category.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Options {
+    pub function: Option<Pattern>,
+    pub signal: Option<Pattern>,
+    pub class_variable: Option<Pattern>,
+    pub local_variable: Option<Pattern>,
+    pub argument: Option<Pattern>,
+    pub loop_variable: Option<Pattern>,
+    pub class_name: Option<Pattern>,
+    pub class: Option<Pattern>,
+    pub r#enum: Option<Pattern>,
+    pub enum_member: Option<Pattern>,
+    pub constant: Option<Pattern>,
+    pub load_constant: Option<Pattern>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Case {
+    Snake,
+    Pascal,
+    Constant,
+}
+
+impl Case {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Case::Snake => "snake_case",
+            Case::Pascal => "PascalCase",
+            Case::Constant => "CONSTANT_CASE",
+        }
+    }
+
+    pub fn is_match(self, name: &str) -> bool {
+        match self {
+            Case::Snake => SNAKE_CASE.is_match(name),
+            Case::Pascal => PASCAL_CASE.is_match(name),
+            Case::Constant => CONSTANT_CASE.is_match(name),
+        }
+    }
+
+    /// Converts a name to this case, keeping a leading underscore.
+    pub fn convert(self, name: &str) -> String {
+        let prefix = match name.starts_with('_') {
+            true => "_",
+            false => "",
+        };
+        let words = words(name);
+        let converted = match self {
+            Case::Snake => words.join("_"),
+            Case::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+            Case::Constant => words.join("_").to_uppercase(),
+        };
+        format!("{prefix}{converted}")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Category {
+    Function,
+    Signal,
+    ClassVariable,
+    LocalVariable,
+    Argument,
+    LoopVariable,
+    ClassName,
+    Class,
+    Enum,
+    EnumMember,
+    Constant,
+    LoadConstant,
+}
+
+impl Category {
+    fn describe(self) -> &'static str {
+        match self {
+            Category::Function => "function name",
+            Category::Signal => "signal name",
+            Category::ClassVariable => "class variable name",
+            Category::LocalVariable => "local variable name",
+            Category::Argument => "argument name",
+            Category::LoopVariable => "loop variable name",
+            Category::ClassName => "class name",
+            Category::Class => "inner class name",
+            Category::Enum => "enum name",
+            Category::EnumMember => "enum member name",
+            Category::Constant => "constant name",
+            Category::LoadConstant => "load constant name",
+        }
+    }
+
+    fn case(self) -> Case {
+        match self {
+            Category::Function
+            | Category::Signal
+            | Category::ClassVariable
+            | Category::LocalVariable
+            | Category::Argument
+            | Category::LoopVariable => Case::Snake,
+            Category::ClassName | Category::Class | Category::Enum | Category::LoadConstant => {
+                Case::Pascal
+            }
+            Category::EnumMember | Category::Constant => Case::Constant,
+        }
+    }
+
+    fn pattern(self, options: &Options) -> Option<&Pattern> {
+        match self {
+            Category::Function => options.function.as_ref(),
+            Category::Signal => options.signal.as_ref(),
+            Category::ClassVariable => options.class_variable.as_ref(),
+            Category::LocalVariable => options.local_variable.as_ref(),
+            Category::Argument => options.argument.as_ref(),
+            Category::LoopVariable => options.loop_variable.as_ref(),
+            Category::ClassName => options.class_name.as_ref(),
+            Category::Class => options.class.as_ref(),
+            Category::Enum => options.r#enum.as_ref(),
+            Category::EnumMember => options.enum_member.as_ref(),
+            Category::Constant => options.constant.as_ref(),
+            Category::LoadConstant => options.load_constant.as_ref(),
+        }
+    }
+}
+
+/// Variables of the script or of an inner class, as opposed to the ones inside of functions.
+fn is_class_variable(variable: Node) -> bool {
+    variable.parent().is_some_and(|parent| {
+        parent.kind() == "source"
+            || parent
+                .parent()
+                .is_some_and(|grandparent| grandparent.kind() == "class_definition")
+    })
+}
+
+/// Constants holding a `load` or `preload` of a script or scene.
+fn is_load_constant(constant: Node, source: &[u8]) -> bool {
+    let Some(call) = constant
+        .child_by_field_name("value")
+        .filter(|value| value.kind() == "call")
+    else {
+        return false;
+    };
+    let is_load = call
+        .named_child(0)
+        .is_some_and(|function| matches!(function.text(source), "load" | "preload"));
+    let path = call
+        .named_child(1)
+        .and_then(|arguments| arguments.named_child(0))
+        .filter(|argument| argument.kind() == "string")
+        .map(|path| path.text(source).trim_matches(['"', '\'']));
+    is_load
+        && path.is_some_and(|path| {
+            CLASS_LIKE_EXTENSIONS
+                .iter()
+                .any(|extension| path.ends_with(extension))
+        })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// The object a member is accessed on, e.g. `other` for the `member` of `other.member` or
+/// `other.member()`. `None` if the node isn't a member.
+fn accessed_object(node: Node) -> Option<Node> {
+    let member = match node.parent()? {
+        call if call.kind() == "attribute_call" => call,
+        _ => node,
+    };
+    member
+        .parent()
+        .filter(|parent| parent.kind() == "attribute")?;
+    // The first child of the attribute is the object itself
+    member.prev_named_sibling()
+}
+
+/// Renames the declaration and everything that refers to it, by name.
+///
+/// Members of other objects are left alone, only members of `self` and of the enums and classes
+/// declared in `local_types` refer to the declarations of this script.
+///
+/// Returns `None` if the new name is taken already.
+fn rename_fix(
+    identifiers: &[Node],
+    local_types: &HashSet<&str>,
+    source: &[u8],
+    from: &str,
+    to: &str,
+) -> Option<Fix> {
+    if identifiers.iter().any(|node| node.text(source) == to) {
+        return None;
+    }
+    let edits = identifiers
+        .iter()
+        .filter(|node| node.text(source) == from)
+        .filter(|node| {
+            accessed_object(**node).is_none_or(|object| {
+                let object = object.text(source);
+                object == "self" || local_types.contains(object)
+            })
+        })
+        .map(|node| Edit::replace(node.byte_range(), to))
+        .collect();
+    // Other scripts and scenes may refer to the name as well
+    Some(Fix::new(Applicability::Unsafe, edits))
+}
+
+/// Splits a name into lowercase words, e.g. `HTTPRequest2d` into `http`, `request2d`.
+fn words(name: &str) -> Vec<String> {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (index, &char) in chars.iter().enumerate() {
+        if char == '_' {
+            words.extend((!word.is_empty()).then(|| std::mem::take(&mut word)));
+            continue;
+        }
+        let prev = index.checked_sub(1).map(|prev| chars[prev]);
+        let next = chars.get(index + 1);
+        // A new word starts at `aB` and at the `B` of `AAb`
+        let starts_word = char.is_uppercase()
+            && prev.is_some_and(|prev| {
+                prev.is_lowercase()
+                    || prev.is_ascii_digit()
+                    || prev.is_uppercase() && next.is_some_and(|next| next.is_lowercase())
+            });
+        if starts_word && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(char.to_lowercase());
+    }
+    words.extend((!word.is_empty()).then_some(word));
+    words
+}
+
+pub fn check_naming_convention(root: Node, source: Arc<str>, ctx: &RuleContext) -> Vec<Report> {
+    assert!(root.kind() == "source", "Expected 'source' node");
+
+    let options: Options = ctx.options();
+    let identifiers = IdentifierQuery::query(root, source.as_bytes())
+        .into_iter()
+        .map(|result| result.identifier)
+        .collect::<Vec<_>>();
+    let mut reports = Vec::new();
+
+    let declarations = NamingQuery::query(root, source.as_bytes());
+    let local_types = declarations
+        .iter()
+        .filter_map(|result| result.enum_.or(result.class).or(result.class_name))
+        .map(|name| name.text(source.as_bytes()))
+        .collect::<HashSet<_>>();
+
+    for result in declarations {
+        let (category, name) = match result {
+            NamingQuery {
+                function: Some(name),
+                ..
+            } => (Category::Function, name),
+            NamingQuery {
+                signal: Some(name), ..
+            } => (Category::Signal, name),
+            NamingQuery {
+                variable: Some(name),
+                ..
+            } => match is_class_variable(name.parent().expect("variable statement")) {
+                true => (Category::ClassVariable, name),
+                false => (Category::LocalVariable, name),
+            },
+            NamingQuery {
+                argument: Some(name),
+                ..
+            } => (Category::Argument, name),
+            NamingQuery {
+                loop_variable: Some(name),
+                ..
+            } => (Category::LoopVariable, name),
+            NamingQuery {
+                class_name: Some(name),
+                ..
+            } => (Category::ClassName, name),
+            NamingQuery {
+                class: Some(name), ..
+            } => (Category::Class, name),
+            NamingQuery {
+                enum_: Some(name), ..
+            } => (Category::Enum, name),
+            NamingQuery {
+                enum_member: Some(name),
+                ..
+            } => (Category::EnumMember, name),
+            NamingQuery {
+                constant: Some(name),
+                ..
+            } => match is_load_constant(name.parent().expect("const statement"), source.as_bytes())
+            {
+                true => (Category::LoadConstant, name),
+                false => (Category::Constant, name),
+            },
+            _ => unreachable!("every pattern has a capture"),
+        };
+
+        let text = name.text(source.as_bytes());
+        let case = category.case();
+        let (matches, expected, help, renamed) = match category.pattern(&options) {
+            Some(Pattern { regex, pattern }) => (
+                regex.is_match(text),
+                format!("match `{pattern}`"),
+                "the pattern is configured in `foli.toml`".to_string(),
+                None,
+            ),
+            None => {
+                let renamed = case.convert(text);
+                (
+                    case.is_match(text),
+                    format!("be {}", case.describe()),
+                    format!("rename it to `{renamed}`"),
+                    Some(renamed).filter(|renamed| case.is_match(renamed)),
+                )
+            }
+        };
+        if matches {
+            continue;
+        }
+
+        let report = miette::miette!(
+            severity = ctx.severity,
+            code = "naming-convention",
+            labels = vec![LabeledSpan::new_primary_with_span(
+                Some(category.describe().into()),
+                name.to_source_span(),
+            )],
+            url = "https://docs.godotengine.org/en/stable/tutorials/scripting/gdscript/\
+                            gdscript_styleguide.html#naming-conventions",
+            help = help,
+            "{} `{text}` should {expected}",
+            category.describe(),
+        )
+        .with_source_code(Arc::clone(&source));
+
+        let fix = renamed.and_then(|renamed| {
+            rename_fix(
+                &identifiers,
+                &local_types,
+                source.as_bytes(),
+                text,
+                &renamed,
+            )
+        });
+        reports.push(match fix {
+            Some(fix) => report.with_fix(fix),
+            None => report,
+        });
+    }
+
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn case_conversion() {
+        assert_eq!(Case::Snake.convert("fooBar"), "foo_bar");
+        assert_eq!(Case::Snake.convert("_FooBar"), "_foo_bar");
+        assert_eq!(Case::Snake.convert("HTTPRequest"), "http_request");
+        assert_eq!(Case::Snake.convert("vector2D"), "vector2_d");
+        assert_eq!(Case::Snake.convert("already_snake"), "already_snake");
+        assert_eq!(
+            Case::Pascal.convert("player_controller"),
+            "PlayerController"
+        );
+        assert_eq!(Case::Pascal.convert("_inner"), "_Inner");
+        assert_eq!(Case::Constant.convert("maxSpeed"), "MAX_SPEED");
+    }
+
+    #[test]
+    fn cases() {
+        for name in ["foo", "_foo", "foo_bar2", "_", "x2"] {
+            assert!(Case::Snake.is_match(name), "{name}");
+        }
+        for name in ["Foo", "__foo", "fooBar", "foo__bar", "foo_", "2foo"] {
+            assert!(!Case::Snake.is_match(name), "{name}");
+        }
+        for name in ["Foo", "HTTPRequest", "_Inner", "Vector2"] {
+            assert!(Case::Pascal.is_match(name), "{name}");
+        }
+        for name in ["foo", "Foo_Bar", "FOO_BAR"] {
+            assert!(!Case::Pascal.is_match(name), "{name}");
+        }
+        for name in ["FOO", "MAX_SPEED", "_PRIVATE", "A2"] {
+            assert!(Case::Constant.is_match(name), "{name}");
+        }
+        for name in ["Foo", "MAX__SPEED", "max_speed"] {
+            assert!(!Case::Constant.is_match(name), "{name}");
+        }
+    }
+
+    /// Applies the fixes of the rule, returns the output and the number of fixes.
+    fn fix(source: &str) -> (String, usize) {
+        let mut parser = tree_sitter::Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let rule = crate::lint::rule_for_code("naming-convention").unwrap();
+        let ctx = RuleContext::new(rule, None, Path::new("test.gd")).unwrap();
+        let reports = check_naming_convention(tree.root_node(), source.into(), &ctx);
+        let fixes = reports.iter().filter_map(crate::lint::fix::fix_of);
+        crate::lint::fix::apply_fixes(source, fixes)
+    }
+
+    #[test]
+    fn renames_references() {
+        let source = "\
+enum Mood { happy }
+const maxSpeed = 1
+const bullet = preload(\"res://bullet.tscn\")
+var m: Mood = Mood.happy
+func f():
+	return maxSpeed + bullet.instantiate()
+";
+        let (output, applied) = fix(source);
+        assert_eq!(applied, 3);
+        assert_eq!(
+            output,
+            "\
+enum Mood { HAPPY }
+const MAX_SPEED = 1
+const Bullet = preload(\"res://bullet.tscn\")
+var m: Mood = Mood.HAPPY
+func f():
+	return MAX_SPEED + Bullet.instantiate()
+"
+        );
+    }
+
+    #[test]
+    fn keeps_members_of_other_objects() {
+        let source = "\
+var maxHealth = 1
+func setHealth(other):
+	self.maxHealth = other.maxHealth
+	other.setHealth(maxHealth)
+	self.setHealth(other.get_node(\"a\").maxHealth)
+";
+        let (output, applied) = fix(source);
+        assert_eq!(applied, 2);
+        assert_eq!(
+            output,
+            "\
+var max_health = 1
+func set_health(other):
+	self.max_health = other.maxHealth
+	other.setHealth(max_health)
+	self.set_health(other.get_node(\"a\").maxHealth)
+"
+        );
+    }
 }
@@ -1,31 +1,101 @@
 use std::sync::Arc;
 
-use miette::{LabeledSpan, Report, Severity};
+use miette::{LabeledSpan, Report};
+use serde::Deserialize;
 use tree_sitter::Node;
 
+use super::{
+    RuleContext,
+    fix::{Applicability, Edit, Fix, WithFix},
+};
 use crate::{NodeExt, query_struct::PrintCallQuery};
 
-pub fn check_no_print_call(root: Node, source: Arc<str>) -> Vec<Report> {
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Options {
+    /// The print functions that are reported, see [`PrintCallQuery`] for the candidates.
+    pub functions: Vec<String>,
+    /// The logging function that replaces the print calls with `--fix-unsafe`, e.g. `Log.info`.
+    pub replacement: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            functions: vec!["print".into()],
+            replacement: None,
+        }
+    }
+}
+
+pub fn check_no_print_call(root: Node, source: Arc<str>, ctx: &RuleContext) -> Vec<Report> {
     assert!(root.kind() == "source", "Expected 'source' node");
 
+    let options: Options = ctx.options();
     let statements = PrintCallQuery::query(root, source.as_bytes());
     let mut reports = Vec::new();
 
     for statement in statements {
-        reports.push(
-            miette::miette!(
-                severity = Severity::Warning,
-                code = "no-print",
-                labels = vec![LabeledSpan::new_with_span(
-                    Some("What were you thinking?!".into()),
-                    statement.print.to_source_span(),
-                )],
-                help = "print statements ruined my marriage and poisoned my well",
-                "calling print is discouraged, use a custom logger instead",
-            )
-            .with_source_code(Arc::clone(&source)),
-        );
+        let name = statement.print.text(source.as_bytes());
+        if !options.functions.iter().any(|function| function == name) {
+            continue;
+        }
+
+        let report = miette::miette!(
+            severity = ctx.severity,
+            code = "no-print",
+            labels = vec![LabeledSpan::new_with_span(
+                Some("What were you thinking?!".into()),
+                statement.print.to_source_span(),
+            )],
+            help = "print statements ruined my marriage and poisoned my well",
+            "calling print is discouraged, use a custom logger instead",
+        )
+        .with_source_code(Arc::clone(&source));
+
+        // The logger might format its arguments differently
+        reports.push(match &options.replacement {
+            Some(replacement) => report.with_fix(Fix::new(
+                Applicability::Unsafe,
+                vec![Edit::replace(statement.print.byte_range(), replacement)],
+            )),
+            None => report,
+        });
     }
 
     reports
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::config::RuleConfig;
+
+    /// The names of the reported functions.
+    fn reported(source: &str, options: &str) -> Vec<String> {
+        let tree = crate::new_parser().unwrap().parse(source, None).unwrap();
+        let config: RuleConfig = toml::from_str(options).unwrap();
+        let rule = crate::lint::rule_for_code("no-print").unwrap();
+        let ctx = RuleContext::new(rule, Some(&config), Path::new("a.gd")).unwrap();
+        check_no_print_call(tree.root_node(), source.into(), &ctx)
+            .iter()
+            .map(|report| {
+                let label = report.labels().unwrap().next().unwrap();
+                source[label.offset()..label.offset() + label.len()].to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn functions() {
+        let source = "func f():\n\tprint(1)\n\tprintt(2)\n\tprinterr(3)\n";
+        assert_eq!(reported(source, ""), ["print"]);
+        assert_eq!(
+            reported(source, "functions = [\"printt\", \"printerr\"]"),
+            ["printt", "printerr"]
+        );
+        assert!(reported(source, "functions = []").is_empty());
+    }
+}
@@ -0,0 +1,374 @@
+//! Inline suppression comments, keyed on the diagnostic codes.
+//!
+//! - `# foli-ignore: no-print` on the same line as the diagnostic or on its own line above it
+//! - `# foli-ignore-file: no-print` anywhere in the file, conventionally at the top
+//! - `# foli-disable: no-print` until the next `# foli-enable: no-print` (or the end of the file)
+//!
+//! Multiple codes are separated by commas, omitting them suppresses every rule.
+
+use std::sync::Arc;
+
+use miette::{LabeledSpan, Report};
+use tree_sitter::Node;
+
+use super::RuleContext;
+use crate::{NodeExt, line_index::LineIndex, node_ext::collect_comments};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// A single (zero-based) line.
+    Line(usize),
+    /// The lines `start..end`.
+    Region {
+        start: usize,
+        end: usize,
+    },
+    File,
+}
+
+#[derive(Debug)]
+struct Suppression<'tree> {
+    comment: Node<'tree>,
+    /// `None` suppresses every rule.
+    code: Option<String>,
+    scope: Scope,
+    used: bool,
+}
+
+impl Suppression<'_> {
+    fn covers(&self, code: &str, line: Option<usize>) -> bool {
+        let code_matches = self.code.as_deref().is_none_or(|c| c == code);
+        let line_matches = match (self.scope, line) {
+            (Scope::File, _) => true,
+            (Scope::Line(l), Some(line)) => l == line,
+            (Scope::Region { start, end }, Some(line)) => (start..end).contains(&line),
+            (_, None) => false,
+        };
+        code_matches && line_matches
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    Ignore,
+    IgnoreFile,
+    Disable,
+    Enable,
+}
+
+/// Parses `foli-ignore: a, b` into the directive and its codes.
+fn parse_directive(comment: &str) -> Option<(Directive, Vec<String>)> {
+    let text = comment.strip_prefix('#')?.trim();
+    // Longest prefix first, `foli-ignore` is a prefix of `foli-ignore-file`
+    let (directive, rest) = [
+        ("foli-ignore-file", Directive::IgnoreFile),
+        ("foli-ignore", Directive::Ignore),
+        ("foli-disable", Directive::Disable),
+        ("foli-enable", Directive::Enable),
+    ]
+    .into_iter()
+    .find_map(|(prefix, directive)| text.strip_prefix(prefix).map(|rest| (directive, rest)))?;
+
+    let codes = match rest.trim() {
+        "" => vec![],
+        rest => rest
+            .strip_prefix(':')?
+            .split(',')
+            .map(str::trim)
+            .filter(|code| !code.is_empty())
+            .map(String::from)
+            .collect(),
+    };
+    Some((directive, codes))
+}
+
+/// The line of the primary label (or the first label if there's none).
+fn report_offset(report: &Report) -> Option<usize> {
+    let labels = report.labels()?.collect::<Vec<_>>();
+    labels
+        .iter()
+        .find(|label| label.primary())
+        .or(labels.first())
+        .map(|label| label.offset())
+}
+
+pub struct Suppressions<'tree> {
+    suppressions: Vec<Suppression<'tree>>,
+    lines: LineIndex,
+}
+
+impl<'tree> Suppressions<'tree> {
+    pub fn parse(root: Node<'tree>, source: &str) -> Self {
+        let lines = LineIndex::new(source);
+        let source_lines = source.lines().collect::<Vec<_>>();
+
+        let mut comments = Vec::new();
+        collect_comments(root, &mut comments);
+
+        let mut suppressions: Vec<Suppression> = Vec::new();
+        // Indices into `suppressions` of regions that haven't been closed yet
+        let mut open_regions: Vec<usize> = Vec::new();
+
+        for comment in comments {
+            let Some((directive, codes)) = parse_directive(comment.text(source.as_bytes())) else {
+                continue;
+            };
+            let line = lines.line(comment.start_byte());
+            let codes = match codes.is_empty() {
+                true => vec![None],
+                false => codes.into_iter().map(Some).collect(),
+            };
+
+            let scope = match directive {
+                Directive::IgnoreFile => Scope::File,
+                Directive::Disable => Scope::Region {
+                    start: line,
+                    end: usize::MAX,
+                },
+                Directive::Ignore => {
+                    let is_trailing = !source_lines[line][..comment.start_position().column]
+                        .trim()
+                        .is_empty();
+                    match is_trailing {
+                        true => Scope::Line(line),
+                        // Applies to the next line with code on it
+                        false => Scope::Line(
+                            (line + 1..source_lines.len())
+                                .find(|&l| {
+                                    let text = source_lines[l].trim();
+                                    !text.is_empty() && !text.starts_with('#')
+                                })
+                                .unwrap_or(line + 1),
+                        ),
+                    }
+                }
+                Directive::Enable => {
+                    open_regions.retain(|&i| {
+                        let suppression = &mut suppressions[i];
+                        let closes = codes
+                            .iter()
+                            .any(|code| code.is_none() || *code == suppression.code);
+                        if closes && let Scope::Region { end, .. } = &mut suppression.scope {
+                            *end = line;
+                        }
+                        !closes
+                    });
+                    continue;
+                }
+            };
+
+            for code in codes {
+                if directive == Directive::Disable {
+                    open_regions.push(suppressions.len());
+                }
+                suppressions.push(Suppression {
+                    comment,
+                    code,
+                    scope,
+                    used: false,
+                });
+            }
+        }
+
+        Self {
+            suppressions,
+            lines,
+        }
+    }
+
+    /// Removes the suppressed reports and remembers which suppressions were used.
+    pub fn apply(&mut self, reports: Vec<Report>) -> Vec<Report> {
+        reports
+            .into_iter()
+            .filter(|report| {
+                let Some(code) = report.code().map(|code| code.to_string()) else {
+                    return true;
+                };
+                let line = report_offset(report).map(|offset| self.lines.line(offset));
+
+                let mut suppressed = false;
+                for suppression in &mut self.suppressions {
+                    if suppression.covers(&code, line) {
+                        suppression.used = true;
+                        suppressed = true;
+                    }
+                }
+                !suppressed
+            })
+            .collect()
+    }
+
+    /// Reports suppressions that didn't suppress anything.
+    ///
+    /// Suppressions for rules that didn't run (because they're disabled) are ignored,
+    /// `ran` tells whether the rule behind a code was run. File-level suppressions of
+    /// `unused-suppression` (or of every rule) silence these reports, a suppression never
+    /// silences its own.
+    pub fn unused(
+        &self,
+        source: Arc<str>,
+        ctx: &RuleContext,
+        ran: impl Fn(&str) -> bool,
+    ) -> Vec<Report> {
+        let mut unused = Vec::new();
+        for (index, suppression) in self.suppressions.iter().enumerate() {
+            if suppression.used {
+                continue;
+            }
+
+            let message = match suppression.code.as_deref() {
+                Some(code) if super::rule_for_code(code).is_none() => {
+                    format!("suppression for unknown rule `{code}`")
+                }
+                Some(code) if !ran(code) => continue,
+                Some(code) => format!("unused suppression for `{code}`"),
+                None => "unused suppression".to_string(),
+            };
+            unused.push((index, message));
+        }
+
+        // The file-level suppressions that silence a report are used after all
+        let mut silencing = Vec::new();
+        unused.retain(|&(index, _)| {
+            let silenced_by = (0..self.suppressions.len()).find(|&other| {
+                other != index
+                    && self.suppressions[other].covers(super::UNUSED_SUPPRESSION.code, None)
+            });
+            silencing.extend(silenced_by);
+            silenced_by.is_none()
+        });
+        unused.retain(|(index, _)| !silencing.contains(index));
+
+        let mut reports = Vec::new();
+        for (index, message) in unused {
+            let suppression = &self.suppressions[index];
+            reports.push(
+                miette::miette!(
+                    severity = ctx.severity,
+                    code = "unused-suppression",
+                    labels = vec![LabeledSpan::new_with_span(
+                        Some("doesn't suppress anything".into()),
+                        suppression.comment.to_source_span(),
+                    )],
+                    help = "remove the code from the comment, or the comment altogether",
+                    "{message}",
+                )
+                .with_source_code(Arc::clone(&source)),
+            );
+        }
+        reports
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directive(comment: &str) -> Option<(Directive, Vec<String>)> {
+        parse_directive(comment)
+    }
+
+    #[test]
+    fn parse_directives() {
+        assert_eq!(
+            directive("# foli-ignore"),
+            Some((Directive::Ignore, vec![]))
+        );
+        assert_eq!(
+            directive("#foli-ignore: no-print, line-length"),
+            Some((
+                Directive::Ignore,
+                vec!["no-print".into(), "line-length".into()]
+            ))
+        );
+        assert_eq!(
+            directive("# foli-ignore-file: no-print"),
+            Some((Directive::IgnoreFile, vec!["no-print".into()]))
+        );
+        assert_eq!(
+            directive("# foli-disable"),
+            Some((Directive::Disable, vec![]))
+        );
+        assert_eq!(
+            directive("# foli-enable: a"),
+            Some((Directive::Enable, vec!["a".into()]))
+        );
+        assert_eq!(directive("# foli-ignored"), None);
+        assert_eq!(directive("# just a comment"), None);
+    }
+
+    fn suppressed_lines(code: &str) -> Vec<usize> {
+        let mut parser = tree_sitter::Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(code, None).unwrap();
+        let suppressions = Suppressions::parse(tree.root_node(), code);
+
+        (0..code.lines().count())
+            .filter(|&line| {
+                suppressions
+                    .suppressions
+                    .iter()
+                    .any(|s| s.covers("no-print", Some(line)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn suppression_scopes() {
+        let code = "func f():\n\tprint(1) # foli-ignore: no-print\n\tprint(2)\n";
+        assert_eq!(suppressed_lines(code), vec![1]);
+
+        let code = "func f():\n\t# foli-ignore\n\t# unrelated\n\tprint(1)\n\tprint(2)\n";
+        assert_eq!(suppressed_lines(code), vec![3]);
+
+        let code = "func f():\n\t# foli-ignore: line-length\n\tprint(1)\n";
+        assert_eq!(suppressed_lines(code), Vec::<usize>::new());
+
+        let code = "# foli-disable: no-print\nfunc f():\n\tprint(1)\n# foli-enable: no-print\n\tprint(2)\n";
+        assert_eq!(suppressed_lines(code), vec![0, 1, 2]);
+
+        let code = "func f():\n\tprint(1)\n# foli-ignore-file: no-print\n";
+        assert_eq!(suppressed_lines(code), vec![0, 1, 2]);
+    }
+
+    /// The codes of all reports of the code, with their (zero-based) lines.
+    fn checked(code: &str) -> Vec<(usize, String)> {
+        let tree = crate::new_parser().unwrap().parse(code, None).unwrap();
+        let rules = crate::config::RuleConfigs::default();
+        let lines = LineIndex::new(code);
+        let mut reports = crate::run_checks(tree.root_node(), code.into(), &rules, "a.gd".as_ref())
+            .iter()
+            .map(|report| {
+                let line = lines.line(report_offset(report).unwrap());
+                (line, report.code().unwrap().to_string())
+            })
+            .collect::<Vec<_>>();
+        reports.sort();
+        reports
+    }
+
+    #[test]
+    fn unused_suppressions() {
+        let code = "func f() -> void:\n\tprint(1) # foli-ignore: no-print\n\tpass # foli-ignore\n";
+        assert_eq!(checked(code), [(2, "unused-suppression".to_string())]);
+
+        // File-level directives cover unused suppressions
+        let ignored = format!("# foli-ignore-file: unused-suppression\n{code}");
+        assert!(checked(&ignored).is_empty());
+        let ignored = format!("# foli-ignore-file\n{code}");
+        assert!(checked(&ignored).is_empty());
+        // But not their own
+        let code = "# foli-ignore-file: no-print\nvar a := 1\n";
+        assert_eq!(checked(code), [(0, "unused-suppression".to_string())]);
+
+        // The diagnostics on lines with syntax errors are dropped, their suppressions aren't unused
+        let code = "func f() -> void:\n\tprint(1 # foli-ignore: no-print\n";
+        assert!(
+            checked(code)
+                .iter()
+                .all(|(_, code)| code == crate::lint::syntax::CODE)
+        );
+    }
+}
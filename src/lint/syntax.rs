@@ -0,0 +1,283 @@
+//! Reports the parts of a file that don't parse.
+//!
+//! Tree-sitter recovers from syntax errors by wrapping the tokens it couldn't make sense of in
+//! `ERROR` nodes and inserting zero-width `MISSING` nodes for tokens it had to assume, the rest
+//! of the tree is still usable. The rules run on the whole tree, but their diagnostics on the
+//! lines of a syntax error are dropped with [`SyntaxErrors::retain_outside`], since they'd
+//! mostly be noise caused by the error.
+
+use std::{ops::Range, sync::Arc};
+
+use miette::{LabeledSpan, MietteDiagnostic, Report, Severity};
+use tree_sitter::{Language, Node};
+
+/// The code of syntax error diagnostics, they can't be configured or suppressed.
+pub const CODE: &str = "syntax-error";
+pub const DESCRIPTION: &str = "The file is valid GDScript";
+
+/// More expected tokens than this aren't helpful anymore.
+const MAX_EXPECTED: usize = 6;
+
+/// Longer skipped code is cut off in the label.
+const MAX_LABEL_CHARS: usize = 30;
+
+/// Named tokens that are worth suggesting, other named kinds are whole constructs.
+const NAMED_TOKENS: &[&str] = &["identifier", "name", "string", "integer", "float"];
+
+/// The syntax errors of a file.
+pub struct SyntaxErrors {
+    pub reports: Vec<Report>,
+    /// The byte ranges of the lines with syntax errors.
+    broken: Vec<Range<usize>>,
+}
+
+impl SyntaxErrors {
+    pub fn check(root: Node, source: Arc<str>) -> Self {
+        let mut errors = Self {
+            reports: Vec::new(),
+            broken: Vec::new(),
+        };
+        if !root.has_error() {
+            return errors;
+        }
+
+        errors.collect(root, &source);
+
+        // Errors the parser recovered from without an `ERROR` or `MISSING` node
+        if errors.reports.is_empty() {
+            errors.reports.push(
+                Report::new(
+                    MietteDiagnostic::new("syntax error")
+                        .with_code(CODE)
+                        .with_severity(Severity::Error),
+                )
+                .with_source_code(Arc::clone(&source)),
+            );
+            errors.broken.push(0..source.len());
+        }
+        errors
+    }
+
+    fn collect(&mut self, node: Node, source: &Arc<str>) {
+        if node.is_error() || node.is_missing() {
+            let diagnostic = match node.is_missing() {
+                true => missing(node),
+                false => unexpected(node, source),
+            };
+            self.reports
+                .push(Report::new(diagnostic).with_source_code(Arc::clone(source)));
+            self.broken
+                .push(line_start(source, node.start_byte())..line_end(source, node.end_byte()));
+            return;
+        }
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect(child, source);
+        }
+    }
+
+    /// Drops the reports with a label on a line with a syntax error.
+    pub fn retain_outside(&self, reports: Vec<Report>) -> Vec<Report> {
+        if self.broken.is_empty() {
+            return reports;
+        }
+        reports
+            .into_iter()
+            .filter(|report| {
+                report.labels().is_none_or(|mut labels| {
+                    labels.all(|label| {
+                        !self
+                            .broken
+                            .iter()
+                            .any(|broken| broken.contains(&label.offset()))
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+/// Whether any of the reports is a syntax error, fixes aren't applied to such files.
+pub fn has_syntax_error(reports: &[Report]) -> bool {
+    reports
+        .iter()
+        .any(|report| report.code().is_some_and(|code| code.to_string() == CODE))
+}
+
+fn missing(node: Node) -> MietteDiagnostic {
+    MietteDiagnostic::new(format!("missing {}", describe_kind(node.kind())))
+        .with_code(CODE)
+        .with_severity(Severity::Error)
+        .with_label(LabeledSpan::new_primary_with_span(
+            Some(format!("expected {} here", describe_kind(node.kind()))),
+            node.start_byte(),
+        ))
+}
+
+fn unexpected(node: Node, source: &str) -> MietteDiagnostic {
+    let diagnostic = MietteDiagnostic::new("syntax error")
+        .with_code(CODE)
+        .with_severity(Severity::Error);
+
+    let Some(token) = first_token(node, source) else {
+        return diagnostic.with_label(LabeledSpan::new_primary_with_span(
+            Some("unexpected end of file".to_string()),
+            node.start_byte(),
+        ));
+    };
+    // The skipped tokens can span several statements, the first line is enough to find them
+    let start = token.start_byte();
+    let end = node.end_byte().min(line_end(source, start));
+    let skipped = source[start..end].trim_end();
+    let text = match skipped.char_indices().nth(MAX_LABEL_CHARS) {
+        Some((i, _)) => format!("{}…", &skipped[..i]),
+        None => skipped.to_string(),
+    };
+    let diagnostic = diagnostic.with_label(LabeledSpan::new_primary_with_span(
+        Some(format!("unexpected `{text}`")),
+        start..start + skipped.len(),
+    ));
+
+    // The state the parser was in when it reached the first skipped token tells what it
+    // expected. If that includes the skipped token, the actual error is somewhere later.
+    let expected = expected_tokens(&node.language(), token.parse_state());
+    if expected.is_empty() || expected.len() > MAX_EXPECTED || expected.contains(&token.kind()) {
+        return diagnostic;
+    }
+    diagnostic.with_help(format!("expected {}", describe_expected(&expected)))
+}
+
+/// The visible tokens that are valid in the given parse state.
+fn expected_tokens(language: &Language, state: u16) -> Vec<&'static str> {
+    let Some(lookahead) = language.lookahead_iterator(state) else {
+        return Vec::new();
+    };
+    let mut expected = lookahead
+        .filter(|&symbol| {
+            language.node_kind_is_visible(symbol)
+                && (!language.node_kind_is_named(symbol)
+                    || language
+                        .node_kind_for_id(symbol)
+                        .is_some_and(|kind| NAMED_TOKENS.contains(&kind)))
+        })
+        .filter_map(|symbol| language.node_kind_for_id(symbol))
+        .collect::<Vec<_>>();
+    expected.sort_unstable();
+    expected.dedup();
+    expected
+}
+
+fn describe_expected(expected: &[&str]) -> String {
+    let described = expected
+        .iter()
+        .map(|kind| describe_kind(kind))
+        .collect::<Vec<_>>();
+    match described.as_slice() {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} or {last}", rest.join(", ")),
+    }
+}
+
+/// Named tokens by what they are, others by their text.
+fn describe_kind(kind: &str) -> String {
+    match kind {
+        "identifier" | "integer" => format!("an {kind}"),
+        _ if NAMED_TOKENS.contains(&kind) => format!("a {kind}"),
+        _ => format!("`{kind}`"),
+    }
+}
+
+/// The first token of the node that isn't whitespace.
+fn first_token<'tree>(node: Node<'tree>, source: &str) -> Option<Node<'tree>> {
+    if node.child_count() == 0 {
+        return (!source[node.byte_range()].trim().is_empty()).then_some(node);
+    }
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .find_map(|child| first_token(child, source))
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn line_end(source: &str, offset: usize) -> usize {
+    source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Vec<String> {
+        let mut parser = tree_sitter::Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        SyntaxErrors::check(tree.root_node(), source.into())
+            .reports
+            .iter()
+            .map(|report| {
+                let labels = report
+                    .labels()
+                    .into_iter()
+                    .flatten()
+                    .map(|label| format!("{}@{}", label.label().unwrap(), label.offset()));
+                std::iter::once(report.to_string())
+                    .chain(labels)
+                    .chain(report.help().map(|help| help.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(check("var a := 1\n").is_empty());
+        assert_eq!(
+            check("func f() -> :\n\tpass\n"),
+            ["syntax error | unexpected `->`@9"]
+        );
+        assert_eq!(
+            check("var x = (1 + 2\nvar y = 3\n"),
+            ["syntax error | unexpected `= (1 + 2`@6"]
+        );
+        assert_eq!(
+            check("enum E {A, 1}\n"),
+            ["syntax error | unexpected `1`@11 | expected an identifier or `}`"]
+        );
+        assert_eq!(
+            check("enum E {A, B\n"),
+            ["missing `}` | expected `}` here@12"]
+        );
+    }
+
+    #[test]
+    fn drops_reports_on_broken_lines() {
+        let source = "var a := 1\nfunc f() -> :\n\tpass\n";
+        let mut parser = tree_sitter::Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let errors = SyntaxErrors::check(tree.root_node(), source.into());
+        let report = |offset: usize| {
+            miette::miette!(
+                labels = vec![LabeledSpan::at(offset..offset + 1, "here")],
+                "x"
+            )
+        };
+        let kept = errors.retain_outside(vec![report(4), report(11), report(26)]);
+        let offsets = kept
+            .iter()
+            .map(|report| report.labels().unwrap().next().unwrap().offset())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [4, 26]);
+    }
+}
@@ -1,26 +1,53 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::BTreeMap, sync::Arc};
 
-use miette::{LabeledSpan, Report, Severity};
+use miette::{LabeledSpan, Report};
+use serde::Deserialize;
 use tree_sitter::Node;
 
+use super::RuleContext;
 use crate::{NodeExt, query_struct::FunctionDefinitionQuery};
 
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Options {
+    /// Require a return type annotation.
+    pub return_type: bool,
+    /// Require a type annotation on every parameter.
+    pub parameters: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            return_type: true,
+            parameters: true,
+        }
+    }
+}
+
 struct Definition<'tree> {
     ret: Option<Node<'tree>>,
     params: Vec<Node<'tree>>,
     param_list: Node<'tree>,
 }
 
-pub fn check_typed_function_signature(root: Node, source: Arc<str>) -> Vec<Report> {
+pub fn check_typed_function_signature(
+    root: Node,
+    source: Arc<str>,
+    ctx: &RuleContext,
+) -> Vec<Report> {
     assert!(root.kind() == "source", "Expected 'source' node");
 
-    // TODO: Explain this, bla bla group matches by function name
+    let options: Options = ctx.options();
+
+    // The query matches once per parameter, so group the matches by function. Keyed by the
+    // offset of the name to report the functions in source order.
     let definitions: Vec<Definition> = {
         let results = FunctionDefinitionQuery::query(root, source.as_bytes());
-        let mut definitions: HashMap<usize, Definition> = HashMap::new();
+        let mut definitions: BTreeMap<usize, Definition> = BTreeMap::new();
         for result in results {
             definitions
-                .entry(result.name.id())
+                .entry(result.name.start_byte())
                 .and_modify(|entry| {
                     if let Some(param) = result.parameters {
                         entry.params.push(param);
@@ -43,7 +70,7 @@ pub fn check_typed_function_signature(root: Node, source: Arc<str>) -> Vec<Repor
     for definition in definitions {
         let mut labels = Vec::new();
 
-        if definition.ret.is_none() {
+        if options.return_type && definition.ret.is_none() {
             labels.push(LabeledSpan::new_with_span(
                 Some("function is missing a return type".into()),
                 definition.param_list.to_source_point_end(),
@@ -52,7 +79,7 @@ pub fn check_typed_function_signature(root: Node, source: Arc<str>) -> Vec<Repor
 
         for param in definition.params {
             let is_typed = param.child_by_field_name("type").is_some();
-            if !is_typed {
+            if options.parameters && !is_typed {
                 labels.push(LabeledSpan::new_with_span(
                     Some("parameter is missing a type annotation".into()),
                     param.to_source_span(),
@@ -63,7 +90,7 @@ pub fn check_typed_function_signature(root: Node, source: Arc<str>) -> Vec<Repor
         if !labels.is_empty() {
             reports.push(
                 miette::miette!(
-                    severity = Severity::Warning,
+                    severity = ctx.severity,
                     code = "typed-function-signature",
                     labels = labels,
                     "function signatures should be fully typed",
@@ -75,3 +102,50 @@ pub fn check_typed_function_signature(root: Node, source: Arc<str>) -> Vec<Repor
 
     reports
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    /// The first line of the reported functions.
+    fn reported(source: &str) -> Vec<&str> {
+        let tree = crate::new_parser().unwrap().parse(source, None).unwrap();
+        let rule = crate::lint::rule_for_code("typed-function-signature").unwrap();
+        let ctx = RuleContext::new(rule, None, Path::new("a.gd")).unwrap();
+        check_typed_function_signature(tree.root_node(), source.into(), &ctx)
+            .iter()
+            .map(|report| {
+                let offset = report.labels().unwrap().next().unwrap().offset();
+                let start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+                source[start..].lines().next().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn source_order() {
+        let source = "\
+func a(x, y):
+\tpass
+func b() -> void:
+\tpass
+func c(x: int, y):
+\tpass
+func d():
+\tpass
+func e(x, y, z):
+\tpass
+";
+        let expected = [
+            "func a(x, y):",
+            "func c(x: int, y):",
+            "func d():",
+            "func e(x, y, z):",
+        ];
+        for _ in 0..10 {
+            assert_eq!(reported(source), expected);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Converts between byte offsets and the positions of the protocol, and from [`Diagnostic`]s to
+//! the diagnostics of the protocol.
+//!
+//! Positions are counted in UTF-16 code units, the only encoding every client supports.
+
+use std::ops::Range;
+
+use lsp_types::{
+    CodeDescription, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag, Location,
+    NumberOrString, Position, TextEdit, Url,
+};
+
+use crate::{
+    document,
+    line_index::LineIndex,
+    lint,
+    output::{Diagnostic, Severity},
+};
+
+pub fn position(source: &str, lines: &LineIndex, offset: usize) -> Position {
+    let line = lines.line(offset);
+    let character = source[lines.line_start(line)..offset]
+        .encode_utf16()
+        .count();
+    Position::new(line as u32, character as u32)
+}
+
+pub fn range(source: &str, lines: &LineIndex, range: Range<usize>) -> lsp_types::Range {
+    lsp_types::Range::new(
+        position(source, lines, range.start),
+        position(source, lines, range.end),
+    )
+}
+
+/// The byte offset of a position, positions past the end of a line or the source are moved back
+/// to the end.
+pub fn offset(source: &str, lines: &LineIndex, position: Position) -> usize {
+    let line = position.line as usize;
+    if line > lines.line(source.len()) {
+        return source.len();
+    }
+    let start = lines.line_start(line);
+    let mut units = 0;
+    for (offset, char) in source[start..].char_indices() {
+        if units >= position.character as usize || char == '\n' || char == '\r' {
+            return start + offset;
+        }
+        units += char.len_utf16();
+    }
+    source.len()
+}
+
+pub fn offsets(source: &str, lines: &LineIndex, range: lsp_types::Range) -> Range<usize> {
+    let start = offset(source, lines, range.start);
+    let end = offset(source, lines, range.end);
+    start..end.max(start)
+}
+
+/// A single edit that turns `old` into `new`, `None` if they're the same.
+pub fn text_edit(old: &str, lines: &LineIndex, new: &str) -> Option<TextEdit> {
+    let (old_range, new_range) = document::changed_range(old, new)?;
+    Some(TextEdit::new(
+        range(old, lines, old_range),
+        new[new_range].to_string(),
+    ))
+}
+
+pub fn diagnostic(
+    uri: &Url,
+    source: &str,
+    lines: &LineIndex,
+    diagnostic: &Diagnostic,
+) -> lsp_types::Diagnostic {
+    let message = match &diagnostic.help {
+        Some(help) => format!("{}\nhelp: {help}", diagnostic.message),
+        None => diagnostic.message.clone(),
+    };
+    // Diagnostics about the whole file are shown at its start
+    let location = diagnostic.location.as_ref();
+    let primary = location.map_or(0..0, |location| location.range.clone());
+    let related = diagnostic
+        .labels
+        .iter()
+        .filter_map(|label| {
+            Some(DiagnosticRelatedInformation {
+                location: Location::new(
+                    uri.clone(),
+                    range(source, lines, label.location.range.clone()),
+                ),
+                message: label.message.clone()?,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    lsp_types::Diagnostic {
+        range: range(source, lines, primary),
+        severity: Some(match diagnostic.severity {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Advice => DiagnosticSeverity::INFORMATION,
+        }),
+        code: Some(NumberOrString::String(diagnostic.code.clone())),
+        code_description: diagnostic
+            .url
+            .as_deref()
+            .and_then(|url| Url::parse(url).ok())
+            .map(|href| CodeDescription { href }),
+        source: Some(env!("CARGO_PKG_NAME").to_string()),
+        message,
+        related_information: (!related.is_empty()).then_some(related),
+        // Editors fade out unnecessary code instead of underlining it
+        tags: (diagnostic.code == lint::UNUSED_SUPPRESSION.code)
+            .then(|| vec![DiagnosticTag::UNNECESSARY]),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let source = "a\r\n😀b\n";
+        let lines = LineIndex::new(source);
+        assert_eq!(position(source, &lines, 7), Position::new(1, 2));
+        assert_eq!(offset(source, &lines, Position::new(1, 2)), 7);
+        assert_eq!(offset(source, &lines, Position::new(1, 3)), 8);
+        // Past the end of the line and the source
+        assert_eq!(offset(source, &lines, Position::new(0, 5)), 1);
+        assert_eq!(offset(source, &lines, Position::new(1, 9)), 8);
+        assert_eq!(offset(source, &lines, Position::new(2, 0)), 9);
+        assert_eq!(offset(source, &lines, Position::new(7, 0)), 9);
+    }
+}
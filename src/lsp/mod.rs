@@ -0,0 +1,589 @@
+//! A language server over stdio: editors show the diagnostics of the lint rules while typing,
+//! apply their fixes as code actions and format scripts with the formatter.
+//!
+//! Open documents are kept as [`Document`]s, a change only reparses the edited part of the
+//! script before the rules run again.
+
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOptions, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, MessageType, OneOf, PublishDiagnosticsParams, ServerCapabilities,
+    ShowMessageParams, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+    WorkspaceEdit,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics, ShowMessage,
+    },
+    request::{CodeActionRequest, Formatting, RangeFormatting, Request as _},
+};
+use serde_json::json;
+
+use crate::{
+    cli,
+    config::{Config, RuleConfigs},
+    document::{self, Document},
+    format::{self, FormatError, FormatOptions},
+    line_index::LineIndex,
+    lint::fix::Applicability,
+    lint_source, new_parser, output, run_checks,
+    summary::ExitStatus,
+};
+
+mod convert;
+
+/// Serves the client on stdin and stdout until it shuts the server down.
+pub fn run(cli: &cli::Cli) -> anyhow::Result<ExitStatus> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(connection, cli.config.clone())?;
+    io_threads
+        .join()
+        .context("the connection to the client broke")?;
+    Ok(ExitStatus::Clean)
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            code_action_kinds: Some(vec![
+                CodeActionKind::QUICKFIX,
+                CodeActionKind::SOURCE_FIX_ALL,
+            ]),
+            ..Default::default()
+        })),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+fn serve(connection: Connection, explicit_config: Option<PathBuf>) -> anyhow::Result<()> {
+    let (id, _params) = connection.initialize_start()?;
+    connection.initialize_finish(
+        id,
+        json!({
+            "capabilities": capabilities(),
+            "serverInfo": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        }),
+    )?;
+
+    let mut server = Server {
+        connection: &connection,
+        explicit_config,
+        parser: new_parser()?,
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = server.request(request);
+                connection.sender.send(response.into())?;
+            }
+            Message::Notification(notification) => {
+                // A broken notification only affects a single document, keep serving the others
+                if let Err(err) = server.notification(notification) {
+                    tracing::error!("{err:#}");
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+struct OpenDocument {
+    path: PathBuf,
+    rules: RuleConfigs,
+    format: FormatOptions,
+    document: Document,
+    /// The diagnostics of the current version, their fixes are offered as code actions.
+    diagnostics: Vec<output::Diagnostic>,
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    explicit_config: Option<PathBuf>,
+    /// Parses all documents, the server handles one message at a time.
+    parser: tree_sitter::Parser,
+    documents: HashMap<Url, OpenDocument>,
+}
+
+impl Server<'_> {
+    fn request(&mut self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            Formatting::METHOD => handle::<Formatting>(request, |params| {
+                self.format(&params.text_document.uri, None)
+            }),
+            RangeFormatting::METHOD => handle::<RangeFormatting>(request, |params| {
+                self.format(&params.text_document.uri, Some(params.range))
+            }),
+            CodeActionRequest::METHOD => {
+                handle::<CodeActionRequest>(request, |params| self.code_actions(params))
+            }
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request {method}"),
+                );
+            }
+        };
+        match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err((code, err)) => Response::new_err(id, code as i32, format!("{err:#}")),
+        }
+    }
+
+    fn notification(&mut self, notification: Notification) -> anyhow::Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => self.open(params(notification)?),
+            DidChangeTextDocument::METHOD => self.change(params(notification)?),
+            DidCloseTextDocument::METHOD => self.close(params(notification)?),
+            _ => Ok(()),
+        }
+    }
+
+    fn notify<N: lsp_types::notification::Notification>(
+        &self,
+        params: N::Params,
+    ) -> anyhow::Result<()> {
+        let notification = Notification::new(N::METHOD.to_string(), params);
+        self.connection
+            .sender
+            .send(notification.into())
+            .context("the connection to the client broke")
+    }
+
+    fn open(&mut self, params: DidOpenTextDocumentParams) -> anyhow::Result<()> {
+        let uri = params.text_document.uri;
+        // Documents that aren't saved yet get the configuration of the working directory
+        let (path, config) = match uri.to_file_path() {
+            Ok(path) => {
+                let config = Config::find_for(self.explicit_config.as_deref(), &path);
+                (path, config)
+            }
+            Err(()) => (
+                PathBuf::from(uri.path()),
+                Config::find(self.explicit_config.as_deref()),
+            ),
+        };
+        let config = config.unwrap_or_else(|err| {
+            let message = format!("{err:#}");
+            tracing::error!("{message}");
+            let _ = self.notify::<ShowMessage>(ShowMessageParams {
+                typ: MessageType::ERROR,
+                message,
+            });
+            Config::default()
+        });
+
+        let mut rules = config.rules_for(&path);
+        if uri.scheme() != "file" {
+            // Without a file name there's nothing to check
+            rules.entry("file-name".to_string()).or_default().enabled = Some(false);
+        }
+        let document = Document::parse(&mut self.parser, params.text_document.text.into())?;
+        self.documents.insert(
+            uri.clone(),
+            OpenDocument {
+                path,
+                rules,
+                format: config.format,
+                document,
+                diagnostics: Vec::new(),
+            },
+        );
+        self.publish(&uri, Some(params.text_document.version))
+    }
+
+    fn change(&mut self, params: DidChangeTextDocumentParams) -> anyhow::Result<()> {
+        let uri = params.text_document.uri;
+        let open = self
+            .documents
+            .get_mut(&uri)
+            .with_context(|| format!("{uri} isn't open"))?;
+        for change in params.content_changes {
+            let document = &mut open.document;
+            match change.range {
+                Some(range) => {
+                    let lines = LineIndex::new(&document.source);
+                    let range = convert::offsets(&document.source, &lines, range);
+                    document.edit(range, &change.text);
+                }
+                // The whole document, only the changed part has to be parsed again anyway
+                None => {
+                    if let Some((old, new)) =
+                        document::changed_range(&document.source, &change.text)
+                    {
+                        document.edit(old, &change.text[new]);
+                    }
+                }
+            }
+        }
+        open.document.reparse(&mut self.parser)?;
+        self.publish(&uri, Some(params.text_document.version))
+    }
+
+    fn close(&mut self, params: DidCloseTextDocumentParams) -> anyhow::Result<()> {
+        let uri = params.text_document.uri;
+        self.documents.remove(&uri);
+        // The diagnostics of closed documents would stay around otherwise
+        self.notify::<PublishDiagnostics>(PublishDiagnosticsParams::new(uri, Vec::new(), None))
+    }
+
+    fn document(&mut self, uri: &Url) -> anyhow::Result<&mut OpenDocument> {
+        self.documents
+            .get_mut(uri)
+            .with_context(|| format!("{uri} isn't open"))
+    }
+
+    /// Lints a document and sends its diagnostics to the client.
+    fn publish(&mut self, uri: &Url, version: Option<i32>) -> anyhow::Result<()> {
+        let open = self.document(uri)?;
+        let source = &open.document.source;
+        let reports = run_checks(
+            open.document.tree.root_node(),
+            Arc::clone(source),
+            &open.rules,
+            &open.path,
+        );
+        open.diagnostics = output::Diagnostic::all(&open.path, source, &reports);
+
+        let lines = LineIndex::new(source);
+        let diagnostics = open
+            .diagnostics
+            .iter()
+            .map(|diagnostic| convert::diagnostic(uri, source, &lines, diagnostic))
+            .collect();
+        self.notify::<PublishDiagnostics>(PublishDiagnosticsParams::new(
+            uri.clone(),
+            diagnostics,
+            version,
+        ))
+    }
+
+    fn format(
+        &mut self,
+        uri: &Url,
+        range: Option<lsp_types::Range>,
+    ) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let open = self
+            .documents
+            .get(uri)
+            .with_context(|| format!("{uri} isn't open"))?;
+        let source = &open.document.source;
+        let lines = LineIndex::new(source);
+        let formatted = match range {
+            Some(range) => {
+                let range = convert::offsets(source, &lines, range);
+                format::format_range(&mut self.parser, source, range, &open.format)
+            }
+            None => format::format_code(&mut self.parser, source, &open.format),
+        };
+        let formatted = match formatted {
+            Ok(formatted) => formatted,
+            // Like `format`, scripts with syntax errors are left as they are
+            Err(FormatError::Syntax) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(
+            convert::text_edit(source, &lines, &formatted)
+                .into_iter()
+                .collect(),
+        ))
+    }
+
+    /// The fixes of the diagnostics in the range, and a fix of all safe fixes.
+    fn code_actions(
+        &mut self,
+        params: CodeActionParams,
+    ) -> anyhow::Result<Option<Vec<CodeActionOrCommand>>> {
+        let uri = &params.text_document.uri;
+        let open = self
+            .documents
+            .get(uri)
+            .with_context(|| format!("{uri} isn't open"))?;
+        let source = &open.document.source;
+        let lines = LineIndex::new(source);
+        let range = convert::offsets(source, &lines, params.range);
+        // Kinds are hierarchical, `source` also asks for `source.fixAll`
+        let wanted = |kind: &CodeActionKind| {
+            params.context.only.as_ref().is_none_or(|only| {
+                only.iter().any(|only| {
+                    kind.as_str() == only.as_str()
+                        || kind.as_str().starts_with(&format!("{}.", only.as_str()))
+                })
+            })
+        };
+        let workspace_edit = |edits: Vec<TextEdit>| WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        };
+
+        let mut actions = Vec::new();
+        if wanted(&CodeActionKind::QUICKFIX) {
+            for diagnostic in &open.diagnostics {
+                let (Some(fix), Some(location)) = (&diagnostic.fix, &diagnostic.location) else {
+                    continue;
+                };
+                if location.range.start > range.end || range.start > location.range.end {
+                    continue;
+                }
+                let title = match fix.applicability {
+                    Applicability::Safe => format!("Fix `{}`", diagnostic.code),
+                    Applicability::Unsafe => {
+                        format!("Fix `{}` (may change the behavior)", diagnostic.code)
+                    }
+                };
+                let edits = fix
+                    .edits
+                    .iter()
+                    .map(|edit| TextEdit {
+                        range: convert::range(source, &lines, edit.location.range.clone()),
+                        new_text: edit.replacement.clone(),
+                    })
+                    .collect();
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![convert::diagnostic(uri, source, &lines, diagnostic)]),
+                    edit: Some(workspace_edit(edits)),
+                    is_preferred: Some(fix.applicability == Applicability::Safe),
+                    ..Default::default()
+                }));
+            }
+        }
+
+        let has_safe_fix = open.diagnostics.iter().any(|diagnostic| {
+            diagnostic
+                .fix
+                .as_ref()
+                .is_some_and(|fix| fix.applicability == Applicability::Safe)
+        });
+        if wanted(&CodeActionKind::SOURCE_FIX_ALL) && has_safe_fix {
+            // Like `--fix`, fixes are applied until they don't enable other fixes anymore
+            let fixed = lint_source(
+                &mut self.parser,
+                &open.rules,
+                &open.path,
+                Arc::clone(source),
+                Some(Applicability::Safe),
+                None,
+            )?;
+            if let Some(edit) = convert::text_edit(source, &lines, &fixed.source) {
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: "Apply all safe fixes".to_string(),
+                    kind: Some(CodeActionKind::SOURCE_FIX_ALL),
+                    edit: Some(workspace_edit(vec![edit])),
+                    ..Default::default()
+                }));
+            }
+        }
+        Ok(Some(actions))
+    }
+}
+
+fn params<T: serde::de::DeserializeOwned>(notification: Notification) -> anyhow::Result<T> {
+    serde_json::from_value(notification.params)
+        .with_context(|| format!("invalid parameters of {}", notification.method))
+}
+
+/// Runs the handler of a request with its parameters.
+fn handle<R: lsp_types::request::Request>(
+    request: Request,
+    handler: impl FnOnce(R::Params) -> anyhow::Result<R::Result>,
+) -> Result<serde_json::Value, (ErrorCode, anyhow::Error)> {
+    let params = serde_json::from_value(request.params)
+        .with_context(|| format!("invalid parameters of {}", request.method))
+        .map_err(|err| (ErrorCode::InvalidParams, err))?;
+    let result = handler(params).map_err(|err| (ErrorCode::RequestFailed, err))?;
+    Ok(serde_json::to_value(result).expect("results are serializable"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Plays the client's part of a session, with the messages written as JSON.
+    struct Client {
+        connection: Connection,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn request(&mut self, method: &str, params: Value) -> Value {
+            self.next_id += 1;
+            let request = Request::new(self.next_id.into(), method.to_string(), params);
+            self.connection.sender.send(request.into()).unwrap();
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Response(response) => {
+                        assert_eq!(response.id, self.next_id.into());
+                        assert!(response.error.is_none(), "{:?}", response.error);
+                        return response.result.unwrap_or_default();
+                    }
+                    Message::Notification(_) => {}
+                    Message::Request(request) => panic!("unexpected request {request:?}"),
+                }
+            }
+        }
+
+        fn notify(&self, method: &str, params: Value) {
+            let notification = Notification::new(method.to_string(), params);
+            self.connection.sender.send(notification.into()).unwrap();
+        }
+
+        /// The codes of the next published diagnostics.
+        fn diagnostics(&self) -> Vec<String> {
+            let message = self.connection.receiver.recv().unwrap();
+            let Message::Notification(notification) = message else {
+                panic!("expected diagnostics, got {message:?}");
+            };
+            assert_eq!(notification.method, PublishDiagnostics::METHOD);
+            notification.params["diagnostics"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|diagnostic| diagnostic["code"].as_str().unwrap().to_string())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn scripted_session() {
+        // The configuration file keeps the discovery from leaving the directory
+        let dir = std::env::temp_dir().join(format!("foli-lsp-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("foli.toml"), "").unwrap();
+        let uri = Url::from_file_path(dir.join("player.gd")).unwrap();
+
+        let (server, client) = Connection::memory();
+        let server = std::thread::spawn(move || serve(server, None));
+        let mut client = Client {
+            connection: client,
+            next_id: 0,
+        };
+
+        let initialized = client.request("initialize", json!({ "capabilities": {} }));
+        assert_eq!(
+            initialized["capabilities"]["documentFormattingProvider"],
+            true
+        );
+        client.notify("initialized", json!({}));
+
+        client.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": "gdscript",
+                    "version": 1,
+                    "text": "func f() -> void:\n\tprint(1)\n\nvar a := 1\n",
+                },
+            }),
+        );
+        assert_eq!(client.diagnostics(), ["top-level-order", "no-print"]);
+
+        // `print(1)` becomes `pass`
+        client.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": 2 },
+                "contentChanges": [{
+                    "range": {
+                        "start": { "line": 1, "character": 1 },
+                        "end": { "line": 1, "character": 9 },
+                    },
+                    "text": "pass",
+                }],
+            }),
+        );
+        assert_eq!(client.diagnostics(), ["top-level-order"]);
+
+        let actions = client.request(
+            "textDocument/codeAction",
+            json!({
+                "textDocument": { "uri": uri },
+                "range": {
+                    "start": { "line": 3, "character": 0 },
+                    "end": { "line": 3, "character": 0 },
+                },
+                "context": { "diagnostics": [] },
+            }),
+        );
+        let titles = actions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|action| action["title"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, ["Fix `top-level-order`", "Apply all safe fixes"]);
+        let edits = &actions[1]["edit"]["changes"][uri.as_str()];
+        assert_eq!(edits.as_array().unwrap().len(), 1);
+
+        client.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": 3 },
+                "contentChanges": [{ "text": "var a=1\nvar b=2\n" }],
+            }),
+        );
+        assert_eq!(client.diagnostics(), Vec::<String>::new());
+
+        let edits = client.request(
+            "textDocument/rangeFormatting",
+            json!({
+                "textDocument": { "uri": uri },
+                "range": {
+                    "start": { "line": 1, "character": 0 },
+                    "end": { "line": 1, "character": 7 },
+                },
+                "options": { "tabSize": 4, "insertSpaces": false },
+            }),
+        );
+        assert_eq!(
+            edits,
+            json!([{
+                "range": {
+                    "start": { "line": 1, "character": 5 },
+                    "end": { "line": 1, "character": 6 },
+                },
+                "newText": " = ",
+            }])
+        );
+        let edits = client.request(
+            "textDocument/formatting",
+            json!({
+                "textDocument": { "uri": uri },
+                "options": { "tabSize": 4, "insertSpaces": false },
+            }),
+        );
+        assert_eq!(edits[0]["newText"], " = 1\nvar b = ");
+
+        client.notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": uri } }),
+        );
+        assert_eq!(client.diagnostics(), Vec::<String>::new());
+
+        client.request("shutdown", Value::Null);
+        client.notify("exit", Value::Null);
+        server.join().unwrap().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
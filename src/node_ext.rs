@@ -1,3 +1,17 @@
+/// Appends the comments below `node` to `comments`, in source order.
+pub fn collect_comments<'tree>(
+    node: tree_sitter::Node<'tree>,
+    comments: &mut Vec<tree_sitter::Node<'tree>>,
+) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            "comment" => comments.push(child),
+            _ => collect_comments(child, comments),
+        }
+    }
+}
+
 pub trait NodeExt {
     fn to_source_span(&self) -> miette::SourceSpan;
     fn to_source_point_start(&self) -> miette::SourceSpan;
@@ -0,0 +1,81 @@
+//! [Workflow commands](https://docs.github.com/en/actions/reference/workflow-commands-for-github-actions)
+//! that make GitHub Actions annotate the diagnostics in pull requests.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use miette::Report;
+
+use super::{Diagnostic, Emitter, Severity};
+
+pub struct Github<W> {
+    pub out: W,
+}
+
+impl<W: Write> Emitter for Github<W> {
+    fn file(&mut self, path: &Path, source: &str, reports: &[Report]) -> io::Result<()> {
+        for diagnostic in Diagnostic::all(path, source, reports) {
+            writeln!(self.out, "{}", command(&diagnostic))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn command(diagnostic: &Diagnostic) -> String {
+    let name = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Advice => "notice",
+    };
+    let mut properties = vec![format!("file={}", escape_property(&diagnostic.path))];
+    if let Some(location) = &diagnostic.location {
+        properties.extend([
+            format!("line={}", location.start.line),
+            format!("col={}", location.start.column),
+            format!("endLine={}", location.end.line),
+            format!("endColumn={}", location.end.column),
+        ]);
+    }
+    properties.push(format!("title={}", escape_property(&diagnostic.code)));
+
+    let message = match &diagnostic.help {
+        Some(help) => format!("{}\nhelp: {help}", diagnostic.message),
+        None => diagnostic.message.clone(),
+    };
+    format!(
+        "::{name} {}::{}",
+        properties.join(","),
+        escape_data(&message)
+    )
+}
+
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cli::OutputFormat,
+        output::tests::{assert_golden, emit},
+    };
+
+    #[test]
+    fn github() {
+        assert_golden("diagnostics.github.txt", &emit(OutputFormat::Github));
+    }
+}
@@ -0,0 +1,92 @@
+//! [Code Quality](https://docs.gitlab.com/ci/testing/code_quality/#code-quality-report-format)
+//! reports for GitLab merge requests.
+//!
+//! GitLab compares the fingerprints of the source and target branch to tell new issues from
+//! fixed ones, so they must not change when unrelated code moves, see [`Diagnostic::all`].
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use miette::Report;
+use serde_json::{Value, json};
+
+use super::{Diagnostic, Emitter, Severity, stable_hash};
+
+pub struct Gitlab<W> {
+    out: W,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<W> Gitlab<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Emitter for Gitlab<W> {
+    fn file(&mut self, path: &Path, source: &str, reports: &[Report]) -> io::Result<()> {
+        self.diagnostics
+            .extend(Diagnostic::all(path, source, reports));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let issues = self.diagnostics.iter().map(issue).collect::<Vec<_>>();
+        serde_json::to_writer_pretty(&mut self.out, &issues)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+fn issue(diagnostic: &Diagnostic) -> Value {
+    let description = match &diagnostic.help {
+        Some(help) => format!("{}, {help}", diagnostic.message),
+        None => diagnostic.message.clone(),
+    };
+    let location = match &diagnostic.location {
+        Some(location) => json!({
+            "path": diagnostic.path,
+            "positions": {
+                "begin": location.start,
+                "end": location.end,
+            },
+        }),
+        None => json!({
+            "path": diagnostic.path,
+            "lines": { "begin": 1 },
+        }),
+    };
+    // The fingerprints have to be unique within the whole report
+    let fingerprint = stable_hash(&[&diagnostic.path, &diagnostic.fingerprint]);
+    json!({
+        "type": "issue",
+        "check_name": diagnostic.code,
+        "description": description,
+        "categories": ["Style"],
+        "severity": match diagnostic.severity {
+            Severity::Error => "major",
+            Severity::Warning => "minor",
+            Severity::Advice => "info",
+        },
+        "fingerprint": format!("{fingerprint:016x}"),
+        "location": location,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cli::OutputFormat,
+        output::tests::{assert_golden, emit},
+    };
+
+    #[test]
+    fn gitlab() {
+        assert_golden("diagnostics.gitlab.json", &emit(OutputFormat::Gitlab));
+    }
+}
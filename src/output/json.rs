@@ -0,0 +1,115 @@
+//! JSON and JSON Lines output, the schema is documented in `docs/output.md`.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use miette::Report;
+use serde::Serialize;
+
+use super::{Diagnostic, Emitter};
+
+/// Bumped on changes that can break consumers, adding fields isn't one.
+const SCHEMA_VERSION: u32 = 1;
+
+/// A single document with all diagnostics, written at the end.
+pub struct Json<W> {
+    out: W,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<W> Json<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    version: u32,
+    diagnostics: &'a [Diagnostic],
+}
+
+impl<W: Write> Emitter for Json<W> {
+    fn file(&mut self, path: &Path, source: &str, reports: &[Report]) -> io::Result<()> {
+        self.diagnostics
+            .extend(Diagnostic::all(path, source, reports));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let document = Document {
+            version: SCHEMA_VERSION,
+            diagnostics: &self.diagnostics,
+        };
+        serde_json::to_writer_pretty(&mut self.out, &document)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+/// One diagnostic per line, written as soon as a file is done.
+pub struct JsonLines<W> {
+    pub out: W,
+}
+
+impl<W: Write> Emitter for JsonLines<W> {
+    fn file(&mut self, path: &Path, source: &str, reports: &[Report]) -> io::Result<()> {
+        for diagnostic in Diagnostic::all(path, source, reports) {
+            serde_json::to_writer(&mut self.out, &diagnostic)?;
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use super::*;
+    use crate::{
+        cli::OutputFormat,
+        config::RuleConfigs,
+        output::tests::{assert_golden, emit},
+    };
+
+    #[test]
+    fn json() {
+        assert_golden("diagnostics.json", &emit(OutputFormat::Json));
+    }
+
+    #[test]
+    fn json_lines() {
+        assert_golden("diagnostics.jsonl", &emit(OutputFormat::JsonLines));
+    }
+
+    /// The labels of the rules end up in the output as they are, they must not be styled.
+    #[test]
+    fn rule_labels() {
+        let source = Arc::<str>::from("func f() -> void:\n\tpass\n\n\nvar a := 1\n");
+        let path = Path::new("order.gd");
+        let reports = crate::check_source(
+            &mut crate::new_parser().unwrap(),
+            &RuleConfigs::default(),
+            Arc::clone(&source),
+            path,
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        let mut json = Json::new(&mut out);
+        json.file(path, &source, &reports).unwrap();
+        json.finish().unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(!out.contains('\u{1b}'), "the output contains escape codes");
+        assert_golden("top-level-order.json", &out);
+    }
+}
@@ -0,0 +1,364 @@
+//! Writes the diagnostics of a lint run in the selected output format.
+//!
+//! The human readable output renders the [`Report`]s with miette, the machine readable formats
+//! are all made from [`Diagnostic`], so they agree on positions, codes and fixes.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    ops::Range,
+    path::Path,
+};
+
+use miette::Report;
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::{
+    cli::OutputFormat,
+    line_index::{LineIndex, Position},
+    lint::fix::{self, Applicability},
+};
+
+mod github;
+mod gitlab;
+mod json;
+mod sarif;
+mod xml;
+
+/// Receives the reports of a lint run and writes them out.
+pub trait Emitter {
+    /// Called with the reports of every file in the order of the files, also if there are none.
+    fn file(&mut self, path: &Path, source: &str, reports: &[Report]) -> io::Result<()>;
+
+    /// Called once after the last file.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+pub fn emitter<'a>(format: OutputFormat, out: impl Write + 'a) -> Box<dyn Emitter + 'a> {
+    match format {
+        OutputFormat::Text => Box::new(Text { out }),
+        OutputFormat::Json => Box::new(json::Json::new(out)),
+        OutputFormat::JsonLines => Box::new(json::JsonLines { out }),
+        OutputFormat::Sarif => Box::new(sarif::Sarif::new(out)),
+        OutputFormat::Github => Box::new(github::Github { out }),
+        OutputFormat::Gitlab => Box::new(gitlab::Gitlab::new(out)),
+        OutputFormat::Checkstyle => Box::new(xml::Checkstyle::new(out)),
+        OutputFormat::Junit => Box::new(xml::Junit::new(out)),
+    }
+}
+
+/// The fancy miette output, grouped by file.
+struct Text<W> {
+    out: W,
+}
+
+impl<W: Write> Emitter for Text<W> {
+    fn file(&mut self, path: &Path, _source: &str, reports: &[Report]) -> io::Result<()> {
+        if reports.is_empty() {
+            return Ok(());
+        }
+        writeln!(self.out, "[{}]", path.display().red())?;
+        for report in reports {
+            writeln!(self.out, "{report:?}")?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// The severity of a diagnostic, miette treats diagnostics without one as errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Advice,
+}
+
+impl From<Option<miette::Severity>> for Severity {
+    fn from(severity: Option<miette::Severity>) -> Self {
+        match severity.unwrap_or(miette::Severity::Error) {
+            miette::Severity::Error => Self::Error,
+            miette::Severity::Warning => Self::Warning,
+            miette::Severity::Advice => Self::Advice,
+        }
+    }
+}
+
+/// A range of the source, both as byte offsets and as lines and columns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Location {
+    /// The byte offsets, the end is exclusive.
+    pub range: Range<usize>,
+    pub start: Position,
+    /// The position right after the range.
+    pub end: Position,
+}
+
+impl Location {
+    fn new(source: &str, lines: &LineIndex, range: Range<usize>) -> Self {
+        Self {
+            start: lines.position(source, range.start),
+            end: lines.position(source, range.end),
+            range,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Label {
+    pub message: Option<String>,
+    pub primary: bool,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Edit {
+    pub location: Location,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Fix {
+    pub applicability: Applicability,
+    pub edits: Vec<Edit>,
+}
+
+/// A report with everything resolved that the machine readable formats need.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    pub help: Option<String>,
+    pub url: Option<String>,
+    /// The path of the file like it was passed on the command line (or found in a directory).
+    pub path: String,
+    /// The primary label (or the first one), missing for diagnostics about the whole file.
+    pub location: Option<Location>,
+    pub labels: Vec<Label>,
+    pub fix: Option<Fix>,
+    /// Identifies the diagnostic within the file across runs, see [`Diagnostic::all`].
+    #[serde(skip)]
+    pub fingerprint: String,
+}
+
+impl Diagnostic {
+    pub fn new(path: &Path, source: &str, lines: &LineIndex, report: &Report) -> Self {
+        let location = |range: Range<usize>| Location::new(source, lines, range);
+
+        let labels = report
+            .labels()
+            .into_iter()
+            .flatten()
+            .map(|label| Label {
+                message: label.label().map(str::to_string),
+                primary: label.primary(),
+                location: location(label.offset()..label.offset() + label.len()),
+            })
+            .collect::<Vec<_>>();
+        let fix = fix::fix_of(report).map(|fix| Fix {
+            applicability: fix.applicability,
+            edits: fix
+                .edits
+                .iter()
+                .map(|edit| Edit {
+                    location: location(edit.range.clone()),
+                    replacement: edit.replacement.clone(),
+                })
+                .collect(),
+        });
+
+        Self {
+            code: report
+                .code()
+                .map_or_else(|| "unknown".to_string(), |code| code.to_string()),
+            severity: report.severity().into(),
+            message: report.to_string(),
+            help: report.help().map(|help| help.to_string()),
+            url: report.url().map(|url| url.to_string()),
+            path: path.to_string_lossy().replace('\\', "/"),
+            location: labels
+                .iter()
+                .find(|label| label.primary)
+                .or(labels.first())
+                .map(|label| label.location.clone()),
+            labels,
+            fix,
+            fingerprint: String::new(),
+        }
+    }
+
+    /// The diagnostics of all reports of a file.
+    ///
+    /// Their fingerprints are made from the code and the text of the lines they point at, not
+    /// the line numbers, so they stay the same when code above them changes. Identical
+    /// diagnostics are told apart by counting them. The path isn't part of the fingerprint, it's
+    /// up to the formats to combine them.
+    pub fn all(path: &Path, source: &str, reports: &[Report]) -> Vec<Self> {
+        let lines = LineIndex::new(source);
+        let mut seen = HashMap::<u64, usize>::new();
+        reports
+            .iter()
+            .map(|report| {
+                let mut diagnostic = Self::new(path, source, &lines, report);
+                let content = diagnostic.location.as_ref().map_or("", |location| {
+                    let start = lines.line_start(lines.line(location.range.start));
+                    let end = source[location.range.end..]
+                        .find('\n')
+                        .map_or(source.len(), |i| location.range.end + i);
+                    source[start..end].trim()
+                });
+                let hash = stable_hash(&[&diagnostic.code, content]);
+                let occurrence = seen.entry(hash).or_default();
+                diagnostic.fingerprint = format!(
+                    "{:016x}",
+                    stable_hash(&[&hash.to_string(), &occurrence.to_string()])
+                );
+                *occurrence += 1;
+                diagnostic
+            })
+            .collect()
+    }
+}
+
+/// FNV-1a, unlike the hashers of the standard library it gives the same hash in every version.
+pub(crate) fn stable_hash(parts: &[&str]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for part in parts {
+        // The length keeps `["ab", "c"]` and `["a", "bc"]` apart
+        for byte in part.len().to_le_bytes().iter().chain(part.as_bytes()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::lint::fix::{Edit, Fix, WithFix};
+
+    /// Two files with reports that use everything the formats have to represent.
+    pub fn emit(format: OutputFormat) -> String {
+        let first = Arc::<str>::from("extends Node\n\nfunc _ready():\n\tprint(\"ä\")\n");
+        let reports = vec![
+            miette::miette!(
+                severity = miette::Severity::Warning,
+                code = "no-print",
+                url = "https://example.com/no-print",
+                labels = vec![miette::LabeledSpan::new_primary_with_span(
+                    Some("called here".to_string()),
+                    30..41,
+                )],
+                help = "use a logger",
+                "calling print is discouraged",
+            )
+            .with_source_code(Arc::clone(&first))
+            .with_fix(Fix::new(
+                Applicability::Unsafe,
+                vec![Edit::replace(30..35, "Log.info")],
+            )),
+            miette::miette!(
+                severity = miette::Severity::Warning,
+                code = "typed-function-signature",
+                labels = vec![
+                    miette::LabeledSpan::new_primary_with_span(
+                        Some("missing return type".to_string()),
+                        27..27,
+                    ),
+                    miette::LabeledSpan::at(19..25, "function"),
+                ],
+                "function signatures should be fully typed",
+            )
+            .with_source_code(Arc::clone(&first)),
+        ];
+        let second = Arc::<str>::from("class_name Player\n");
+        let second_reports = vec![
+            miette::miette!(
+                severity = miette::Severity::Error,
+                code = "file-name",
+                "file name `Player.gd` should be snake_case",
+            )
+            .with_source_code(Arc::clone(&second)),
+        ];
+
+        let mut out = Vec::new();
+        let mut emitter = emitter(format, &mut out);
+        emitter
+            .file(Path::new("scripts/main.gd"), &first, &reports)
+            .unwrap();
+        emitter.file(Path::new("clean.gd"), "", &[]).unwrap();
+        emitter
+            .file(Path::new("scripts/Player.gd"), &second, &second_reports)
+            .unwrap();
+        emitter.finish().unwrap();
+        drop(emitter);
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Compares the output with the golden file in `src/output/golden/`, set `FOLI_BLESS=1` to
+    /// update the golden files instead.
+    pub fn assert_golden(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/output/golden")
+            .join(name);
+        if std::env::var_os("FOLI_BLESS").is_some() {
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("failed to read {}: {err}", path.display()));
+        assert_eq!(actual, expected, "{name} doesn't match the golden file");
+    }
+
+    #[test]
+    fn locations() {
+        let source = "extends Node\n\nfunc _ready():\n\tprint(\"ä\")\n";
+        let lines = LineIndex::new(source);
+        let location = Location::new(source, &lines, 30..41);
+        assert_eq!(&source[location.range.clone()], "print(\"ä\")");
+        assert_eq!(location.start, Position { line: 4, column: 2 });
+        assert_eq!(
+            location.end,
+            Position {
+                line: 4,
+                column: 12
+            }
+        );
+    }
+
+    #[test]
+    fn fingerprints_survive_line_shifts() {
+        let fingerprints = |source: &str| {
+            let source = Arc::<str>::from(source);
+            let offset = source.find("print").unwrap();
+            let report = || {
+                miette::miette!(
+                    code = "no-print",
+                    labels = vec![miette::LabeledSpan::at(offset..offset + 5, "here")],
+                    "print",
+                )
+                .with_source_code(Arc::clone(&source))
+            };
+            Diagnostic::all(Path::new("a.gd"), &source, &[report(), report()])
+                .into_iter()
+                .map(|diagnostic| diagnostic.fingerprint)
+                .collect::<Vec<_>>()
+        };
+        let before = fingerprints("func f():\n\tprint(1)\n");
+        assert_ne!(before[0], before[1]);
+        assert_eq!(
+            before,
+            fingerprints("var a := 1\n\nfunc f():\n    print(1)\n")
+        );
+        assert_ne!(before, fingerprints("func f():\n\tprint(2)\n"));
+    }
+}
@@ -0,0 +1,240 @@
+//! [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html) output for
+//! code scanning integrations.
+//!
+//! Every diagnostic code becomes a `reportingDescriptor` of the driver, in the order they first
+//! appear, so results can refer to them by index.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use miette::Report;
+use serde_json::{Value, json};
+
+use super::{Diagnostic, Emitter, Location, Severity};
+use crate::lint;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const INFORMATION_URI: &str = "https://github.com/cryeprecision/gdscript-foli";
+
+pub struct Sarif<W> {
+    out: W,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<W> Sarif<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Emitter for Sarif<W> {
+    fn file(&mut self, path: &Path, source: &str, reports: &[Report]) -> io::Result<()> {
+        self.diagnostics
+            .extend(Diagnostic::all(path, source, reports));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut self.out, &log(&self.diagnostics))?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+fn log(diagnostics: &[Diagnostic]) -> Value {
+    let mut codes = Vec::<&str>::new();
+    for diagnostic in diagnostics {
+        if !codes.contains(&diagnostic.code.as_str()) {
+            codes.push(&diagnostic.code);
+        }
+    }
+    let rules = codes
+        .iter()
+        .map(|&code| {
+            let mut rule = json!({ "id": code });
+            if let Some(description) = lint::describe_code(code) {
+                rule["shortDescription"] = json!({ "text": description });
+            }
+            // The url is the same for all diagnostics of a code
+            if let Some(url) = diagnostics
+                .iter()
+                .find(|diagnostic| diagnostic.code == code)
+                .and_then(|diagnostic| diagnostic.url.as_deref())
+            {
+                rule["helpUri"] = json!(url);
+            }
+            rule
+        })
+        .collect::<Vec<_>>();
+
+    let results = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let index = codes
+                .iter()
+                .position(|&code| code == diagnostic.code)
+                .expect("all codes have a rule");
+            result(diagnostic, index)
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": INFORMATION_URI,
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+fn result(diagnostic: &Diagnostic, rule_index: usize) -> Value {
+    let text = match &diagnostic.help {
+        Some(help) => format!("{}\nhelp: {help}", diagnostic.message),
+        None => diagnostic.message.clone(),
+    };
+    let mut location = json!({
+        "physicalLocation": physical_location(&diagnostic.path, diagnostic.location.as_ref()),
+    });
+    if let Some(message) = diagnostic
+        .labels
+        .iter()
+        .find(|label| Some(&label.location) == diagnostic.location.as_ref())
+        .and_then(|label| label.message.as_deref())
+    {
+        location["message"] = json!({ "text": message });
+    }
+
+    let mut result = json!({
+        "ruleId": diagnostic.code,
+        "ruleIndex": rule_index,
+        "level": match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Advice => "note",
+        },
+        "message": { "text": text },
+        "locations": [location],
+    });
+
+    let related = diagnostic
+        .labels
+        .iter()
+        .filter(|label| Some(&label.location) != diagnostic.location.as_ref())
+        .enumerate()
+        .map(|(id, label)| {
+            let mut location = json!({
+                "id": id,
+                "physicalLocation": physical_location(&diagnostic.path, Some(&label.location)),
+            });
+            if let Some(message) = &label.message {
+                location["message"] = json!({ "text": message });
+            }
+            location
+        })
+        .collect::<Vec<_>>();
+    if !related.is_empty() {
+        result["relatedLocations"] = json!(related);
+    }
+
+    if let Some(fix) = &diagnostic.fix {
+        let replacements = fix
+            .edits
+            .iter()
+            .map(|edit| {
+                json!({
+                    "deletedRegion": region(&edit.location),
+                    "insertedContent": { "text": edit.replacement },
+                })
+            })
+            .collect::<Vec<_>>();
+        let description = diagnostic.help.as_deref().unwrap_or(&diagnostic.message);
+        result["fixes"] = json!([{
+            "description": { "text": description },
+            "artifactChanges": [{
+                "artifactLocation": artifact_location(&diagnostic.path),
+                "replacements": replacements,
+            }],
+        }]);
+    }
+    result
+}
+
+fn physical_location(path: &str, location: Option<&Location>) -> Value {
+    let mut physical = json!({ "artifactLocation": artifact_location(path) });
+    if let Some(location) = location {
+        physical["region"] = region(location);
+    }
+    physical
+}
+
+fn artifact_location(path: &str) -> Value {
+    match path.starts_with('/') {
+        true => json!({ "uri": format!("file://{}", encode_uri_path(path)) }),
+        false => json!({ "uri": encode_uri_path(path), "uriBaseId": "%SRCROOT%" }),
+    }
+}
+
+fn region(location: &Location) -> Value {
+    json!({
+        "startLine": location.start.line,
+        "startColumn": location.start.column,
+        "endLine": location.end.line,
+        "endColumn": location.end.column,
+        "byteOffset": location.range.start,
+        "byteLength": location.range.len(),
+    })
+}
+
+/// Percent-encodes everything but unreserved characters and slashes.
+fn encode_uri_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::OutputFormat,
+        output::tests::{assert_golden, emit},
+    };
+
+    #[test]
+    fn sarif() {
+        assert_golden("diagnostics.sarif", &emit(OutputFormat::Sarif));
+    }
+
+    #[test]
+    fn uris() {
+        assert_eq!(
+            artifact_location("my scripts/a.gd"),
+            json!({ "uri": "my%20scripts/a.gd", "uriBaseId": "%SRCROOT%" })
+        );
+        assert_eq!(
+            artifact_location("/home/ä.gd"),
+            json!({ "uri": "file:///home/%C3%A4.gd" })
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Checkstyle and JUnit XML, understood by most CI servers and code review tools.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    path::Path,
+};
+
+use miette::Report;
+
+use super::{Diagnostic, Emitter, Severity};
+
+const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+
+/// The diagnostics of a file, in the order of the files.
+type Files = Vec<(String, Vec<Diagnostic>)>;
+
+fn add_file(files: &mut Files, path: &Path, source: &str, reports: &[Report]) {
+    files.push((
+        path.to_string_lossy().replace('\\', "/"),
+        Diagnostic::all(path, source, reports),
+    ));
+}
+
+/// The [Checkstyle](https://checkstyle.org/) XML format, only files with diagnostics are listed.
+pub struct Checkstyle<W> {
+    out: W,
+    files: Files,
+}
+
+impl<W> Checkstyle<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            files: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Emitter for Checkstyle<W> {
+    fn file(&mut self, path: &Path, source: &str, reports: &[Report]) -> io::Result<()> {
+        add_file(&mut self.files, path, source, reports);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let mut xml = format!("{HEADER}\n<checkstyle version=\"4.3\">\n");
+        for (path, diagnostics) in &self.files {
+            if diagnostics.is_empty() {
+                continue;
+            }
+            writeln!(xml, "  <file name=\"{}\">", escape(path)).unwrap();
+            for diagnostic in diagnostics {
+                let (line, column) = diagnostic.location.as_ref().map_or((1, 1), |location| {
+                    (location.start.line, location.start.column)
+                });
+                let severity = match diagnostic.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                    Severity::Advice => "info",
+                };
+                writeln!(
+                    xml,
+                    "    <error line=\"{line}\" column=\"{column}\" severity=\"{severity}\" message=\"{}\" source=\"{}\"/>",
+                    escape(&message(diagnostic)),
+                    escape(&diagnostic.code),
+                )
+                .unwrap();
+            }
+            xml.push_str("  </file>\n");
+        }
+        xml.push_str("</checkstyle>\n");
+        self.out.write_all(xml.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+/// JUnit XML with a test suite per file and a failed test case per diagnostic, files without
+/// diagnostics have a single passing test case.
+pub struct Junit<W> {
+    out: W,
+    files: Files,
+}
+
+impl<W> Junit<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            files: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Emitter for Junit<W> {
+    fn file(&mut self, path: &Path, source: &str, reports: &[Report]) -> io::Result<()> {
+        add_file(&mut self.files, path, source, reports);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let tests = |diagnostics: &[Diagnostic]| diagnostics.len().max(1);
+        let total_tests = self
+            .files
+            .iter()
+            .map(|(_, diagnostics)| tests(diagnostics))
+            .sum::<usize>();
+        let total_failures = self
+            .files
+            .iter()
+            .map(|(_, diagnostics)| diagnostics.len())
+            .sum::<usize>();
+
+        let mut xml = format!(
+            "{HEADER}\n<testsuites name=\"{}\" tests=\"{total_tests}\" failures=\"{total_failures}\">\n",
+            env!("CARGO_PKG_NAME"),
+        );
+        for (path, diagnostics) in &self.files {
+            let path = escape(path);
+            writeln!(
+                xml,
+                "  <testsuite name=\"{path}\" tests=\"{}\" failures=\"{}\">",
+                tests(diagnostics),
+                diagnostics.len(),
+            )
+            .unwrap();
+            if diagnostics.is_empty() {
+                writeln!(xml, "    <testcase name=\"lint\" classname=\"{path}\"/>").unwrap();
+            }
+            for diagnostic in diagnostics {
+                let (name, position, line) = match &diagnostic.location {
+                    Some(location) => (
+                        format!(
+                            "{} at {}:{}",
+                            diagnostic.code, location.start.line, location.start.column
+                        ),
+                        format!(":{}:{}", location.start.line, location.start.column),
+                        location.start.line,
+                    ),
+                    None => (diagnostic.code.clone(), String::new(), 1),
+                };
+                writeln!(
+                    xml,
+                    "    <testcase name=\"{}\" classname=\"{path}\" file=\"{path}\" line=\"{line}\">",
+                    escape(&name),
+                )
+                .unwrap();
+                writeln!(
+                    xml,
+                    "      <failure message=\"{}\" type=\"{}\">{path}{position}: {}</failure>",
+                    escape(&diagnostic.message),
+                    escape(&diagnostic.code),
+                    escape(&message(diagnostic)),
+                )
+                .unwrap();
+                xml.push_str("    </testcase>\n");
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        self.out.write_all(xml.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+fn message(diagnostic: &Diagnostic) -> String {
+    match &diagnostic.help {
+        Some(help) => format!("{} ({help})", diagnostic.message),
+        None => diagnostic.message.clone(),
+    }
+}
+
+/// Escapes text for attributes and elements, characters XML 1.0 doesn't allow are dropped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\t' => escaped.push_str("&#9;"),
+            '\r' => escaped.push_str("&#13;"),
+            _ if char.is_control() && char < ' ' => {}
+            _ => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::OutputFormat,
+        output::tests::{assert_golden, emit},
+    };
+
+    #[test]
+    fn checkstyle() {
+        assert_golden(
+            "diagnostics.checkstyle.xml",
+            &emit(OutputFormat::Checkstyle),
+        );
+    }
+
+    #[test]
+    fn junit() {
+        assert_golden("diagnostics.junit.xml", &emit(OutputFormat::Junit));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            escape("<a href=\"x\">'&'</a>\n\u{1}"),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;&#10;"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Processes files on several threads while keeping the output in order.
+
+use std::{
+    collections::BTreeMap,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+/// The number of threads used if `--jobs` isn't given.
+pub fn default_jobs() -> NonZeroUsize {
+    thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+}
+
+/// Maps the items on up to `jobs` threads, each thread has its own state created by `init`
+/// (e.g. a parser).
+///
+/// `consume` is called on the calling thread in the order of the items, no matter which thread
+/// finishes first, so the output is the same for every run.
+pub fn map_ordered<'a, T, S, R>(
+    items: &'a [T],
+    jobs: NonZeroUsize,
+    init: impl Fn() -> S + Sync,
+    map: impl Fn(&mut S, &T) -> R + Sync,
+    mut consume: impl FnMut(&'a T, R),
+) where
+    T: Sync,
+    R: Send,
+{
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.get().min(items.len()) {
+            let sender = sender.clone();
+            let (next, init, map) = (&next, &init, &map);
+            scope.spawn(move || {
+                let mut state = init();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    if sender.send((index, map(&mut state, item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Results arrive in any order, they're held back until the ones before them are done
+        let mut pending = BTreeMap::new();
+        let mut current = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&current) {
+                consume(&items[current], result);
+                current += 1;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_order() {
+        let items = (0..200).collect::<Vec<u64>>();
+        let mut seen = Vec::new();
+        map_ordered(
+            &items,
+            NonZeroUsize::new(8).unwrap(),
+            || (),
+            |_, &item| {
+                // Make later items finish first every now and then
+                thread::sleep(std::time::Duration::from_micros((200 - item) % 7));
+                item * 2
+            },
+            |&item, doubled| seen.push((item, doubled)),
+        );
+        let expected = items
+            .iter()
+            .map(|&item| (item, item * 2))
+            .collect::<Vec<_>>();
+        assert_eq!(seen, expected);
+    }
+}
@@ -1,5 +1,7 @@
 use miette::{LabeledSpan, Severity};
-use tree_sitter::{Node, QueryCapture, QueryError};
+use std::cell::RefCell;
+
+use tree_sitter::{Node, QueryCapture, QueryCursor, QueryError};
 
 // A trait to convert from captured nodes to the right type
 trait FromNodeCapture<'tree> {
@@ -41,6 +43,11 @@ fn format_query_error(err: QueryError, source: &[u8]) -> miette::Report {
     .with_source_code(source.to_owned())
 }
 
+thread_local! {
+    /// Reused by all queries, they collect their matches before returning so they never overlap.
+    static QUERY_CURSOR: RefCell<QueryCursor> = RefCell::new(QueryCursor::new());
+}
+
 // A macro to define query structs with their field mappings
 macro_rules! define_query_struct {
     (
@@ -77,12 +84,23 @@ macro_rules! define_query_struct {
         }
 
         impl<'tree> $name<'tree> {
+            /// Compiled on first use and shared by all threads.
+            fn compiled() -> &'static ::tree_sitter::Query {
+                static QUERY: ::std::sync::LazyLock<::tree_sitter::Query> =
+                    ::std::sync::LazyLock::new(|| {
+                        let query_str = $query_str;
+                        ::tree_sitter::Query::new(
+                            &::tree_sitter_gdscript::LANGUAGE.into(),
+                            query_str,
+                        )
+                        .map_err(|err| format_query_error(err, query_str.as_bytes()))
+                        .expect("valid query")
+                    });
+                &QUERY
+            }
+
             pub fn query(root: ::tree_sitter::Node<'tree>, source: &[u8]) -> ::std::vec::Vec<Self> {
-                let query_str = $query_str;
-                let query = ::tree_sitter::Query::new(
-                    &::tree_sitter_gdscript::LANGUAGE.into(),
-                    query_str,
-                ).map_err(|err| format_query_error(err, query_str.as_bytes())).expect("valid query");
+                let query = Self::compiled();
                 let capture_count = query.capture_names().len();
 
                 $(
@@ -90,35 +108,36 @@ macro_rules! define_query_struct {
                     .expect(&format!("valid capture index for {}", $capture)) as usize;
                 )*
 
-                let mut query_cursor = ::tree_sitter::QueryCursor::new();
-                query_cursor.set_max_start_depth($max_depth);
+                QUERY_CURSOR.with_borrow_mut(|query_cursor| {
+                    query_cursor.set_max_start_depth($max_depth);
 
-                use ::tree_sitter::StreamingIterator;
-                let mut query_matches = query_cursor.matches(&query, root, source);
+                    use ::tree_sitter::StreamingIterator;
+                    let mut query_matches = query_cursor.matches(query, root, source);
 
-                let mut results = ::std::vec::Vec::new();
-                while let Some(match_) = query_matches.next() {
-                    let mut captures = vec![::std::option::Option::None; capture_count];
-                    for capture in match_.captures {
-                        $(
-                        if capture.index as usize == $field {
-                            captures[$field] = Some(capture);
-                            continue;
+                    let mut results = ::std::vec::Vec::new();
+                    while let Some(match_) = query_matches.next() {
+                        let mut captures = vec![::std::option::Option::None; capture_count];
+                        for capture in match_.captures {
+                            $(
+                            if capture.index as usize == $field {
+                                captures[$field] = Some(capture);
+                                continue;
+                            }
+                            )*
+                            panic!("unexpected capture index: {}", capture.index);
                         }
-                        )*
-                        panic!("unexpected capture index: {}", capture.index);
+                        results.push(Self {
+                            match_id: match_.id() as usize,
+                            $(
+                            $field: match_field_type::<$type>(
+                                captures[$field],
+                                stringify!($field),
+                            ),
+                            )*
+                        });
                     }
-                    results.push(Self {
-                        match_id: match_.id() as usize,
-                        $(
-                        $field: match_field_type::<$type>(
-                            captures[$field],
-                            stringify!($field),
-                        ),
-                        )*
-                    });
-                }
-                results
+                    results
+                })
             }
         }
     };
@@ -157,7 +176,9 @@ define_query_struct!(
     PrintCallQuery,
     r#"
         (call
-            (identifier) @print (#eq? @print "print")
+            (identifier) @print
+            (#any-of? @print
+                "print" "prints" "printt" "printraw" "print_rich" "print_debug" "printerr")
             (arguments (_)))
     "#,
     {
@@ -165,3 +186,46 @@ define_query_struct!(
     },
     max_start_depth = None
 );
+
+define_query_struct!(
+    NamingQuery,
+    r#"
+        [
+            (function_definition name: (name) @function)
+            (signal_statement (name) @signal)
+            (variable_statement name: (name) @variable)
+            (parameters (identifier) @argument)
+            (parameters (_ . (identifier) @argument))
+            (for_statement left: (identifier) @loop_variable)
+            (class_name_statement (name) @class_name)
+            (class_definition name: (name) @class)
+            (enum_definition name: (name) @enum)
+            (enumerator left: (identifier) @enum_member)
+            (const_statement name: (name) @constant)
+        ]
+    "#,
+    {
+        function: "function" => Option<Node<'tree>>,
+        signal: "signal" => Option<Node<'tree>>,
+        variable: "variable" => Option<Node<'tree>>,
+        argument: "argument" => Option<Node<'tree>>,
+        loop_variable: "loop_variable" => Option<Node<'tree>>,
+        class_name: "class_name" => Option<Node<'tree>>,
+        class: "class" => Option<Node<'tree>>,
+        enum_: "enum" => Option<Node<'tree>>,
+        enum_member: "enum_member" => Option<Node<'tree>>,
+        constant: "constant" => Option<Node<'tree>>,
+    },
+    max_start_depth = None
+);
+
+define_query_struct!(
+    IdentifierQuery,
+    r#"
+        [(identifier) (name)] @identifier
+    "#,
+    {
+        identifier: "identifier" => Node<'tree>,
+    },
+    max_start_depth = None
+);
@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+
+use miette::Severity;
+use owo_colors::OwoColorize;
+
+use crate::lint::{
+    self,
+    fix::{self, Applicability},
+};
+
+/// The exit codes of the process, ordered by precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExitStatus {
+    /// No issues (or only warnings within `--max-warnings`).
+    Clean = 0,
+    /// Only warnings were found, or files would be reformatted.
+    Warnings = 1,
+    /// At least one error was found.
+    Errors = 2,
+    /// The tool itself failed, e.g. a file couldn't be read or parsed.
+    Failure = 3,
+}
+
+impl From<ExitStatus> for std::process::ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        std::process::ExitCode::from(status as u8)
+    }
+}
+
+/// Collects statistics over all linted files.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub files: usize,
+    pub failures: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub advice: usize,
+    pub by_rule: BTreeMap<String, usize>,
+    /// Issues that were fixed with `--fix`.
+    pub fixed: usize,
+    /// Remaining issues with a fix, by applicability.
+    pub fixable: BTreeMap<Applicability, usize>,
+}
+
+impl Summary {
+    pub fn record(&mut self, reports: &[miette::Report]) {
+        self.files += 1;
+        // Fixes aren't applied to files with syntax errors
+        let fixable = !lint::syntax::has_syntax_error(reports);
+        for report in reports {
+            // miette treats diagnostics without a severity as errors
+            match report.severity().unwrap_or(Severity::Error) {
+                Severity::Error => self.errors += 1,
+                Severity::Warning => self.warnings += 1,
+                Severity::Advice => self.advice += 1,
+            }
+            let code = report
+                .code()
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "unknown".into());
+            *self.by_rule.entry(code).or_default() += 1;
+            if let Some(fix) = fix::fix_of(report).filter(|_| fixable) {
+                *self.fixable.entry(fix.applicability).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn record_failure(&mut self) {
+        self.files += 1;
+        self.failures += 1;
+    }
+
+    pub fn issues(&self) -> usize {
+        self.errors + self.warnings + self.advice
+    }
+
+    pub fn exit_status(&self, max_warnings: Option<usize>) -> ExitStatus {
+        if self.failures > 0 {
+            ExitStatus::Failure
+        } else if self.errors > 0 {
+            ExitStatus::Errors
+        } else if self.warnings > max_warnings.unwrap_or(0) {
+            ExitStatus::Warnings
+        } else {
+            ExitStatus::Clean
+        }
+    }
+
+    /// Prints the summary to stderr, so it doesn't get mixed up with the diagnostics.
+    ///
+    /// `fix_hints` tells whether to point out the issues `--fix` can fix, fixes can't be applied
+    /// to a script from stdin.
+    pub fn print(&self, fix_hints: bool) {
+        eprintln!(
+            "Checked {} files: {} errors, {} warnings, {} advice",
+            self.files,
+            self.errors.red(),
+            self.warnings.yellow(),
+            self.advice.cyan(),
+        );
+
+        let width = self.by_rule.keys().map(String::len).max().unwrap_or(0);
+        for (code, count) in &self.by_rule {
+            eprintln!("  {code:<width$}  {count}");
+        }
+
+        if self.fixed > 0 {
+            eprintln!("🔧 Fixed {} issues.", self.fixed);
+        }
+        let safe = self.fixable.get(&Applicability::Safe).copied().unwrap_or(0);
+        let unsafe_ = self
+            .fixable
+            .get(&Applicability::Unsafe)
+            .copied()
+            .unwrap_or(0);
+        match (safe, unsafe_) {
+            _ if !fix_hints => {}
+            (0, 0) => {}
+            (safe, 0) => eprintln!("{safe} issues are fixable with `--fix`."),
+            (0, unsafe_) => eprintln!("{unsafe_} issues are fixable with `--fix-unsafe`."),
+            (safe, unsafe_) => eprintln!(
+                "{safe} issues are fixable with `--fix` ({unsafe_} more with `--fix-unsafe`)."
+            ),
+        }
+
+        if self.failures > 0 {
+            eprintln!("💥 Failed to process {} files.", self.failures);
+        } else if self.issues() == 0 {
+            eprintln!("✅ You're good to go!");
+        } else {
+            eprintln!("❌ Found {} issues.", self.issues());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(failures: usize, errors: usize, warnings: usize, advice: usize) -> Summary {
+        Summary {
+            failures,
+            errors,
+            warnings,
+            advice,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exit_statuses() {
+        assert_eq!(summary(0, 0, 0, 0).exit_status(None), ExitStatus::Clean);
+        // Advice never fails
+        assert_eq!(summary(0, 0, 0, 5).exit_status(None), ExitStatus::Clean);
+        assert_eq!(summary(0, 0, 1, 0).exit_status(None), ExitStatus::Warnings);
+        assert_eq!(summary(0, 1, 1, 0).exit_status(None), ExitStatus::Errors);
+        assert_eq!(summary(1, 1, 1, 0).exit_status(None), ExitStatus::Failure);
+    }
+
+    #[test]
+    fn max_warnings() {
+        let summary = summary(0, 0, 3, 0);
+        assert_eq!(summary.exit_status(Some(0)), ExitStatus::Warnings);
+        assert_eq!(summary.exit_status(Some(2)), ExitStatus::Warnings);
+        assert_eq!(summary.exit_status(Some(3)), ExitStatus::Clean);
+        assert_eq!(summary.exit_status(Some(4)), ExitStatus::Clean);
+        // Errors fail no matter how many warnings are allowed
+        let summary = Summary {
+            errors: 1,
+            ..summary
+        };
+        assert_eq!(summary.exit_status(Some(10)), ExitStatus::Errors);
+    }
+}
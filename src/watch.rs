@@ -0,0 +1,312 @@
+//! `lint --watch` lints the files, then lints them again whenever they change.
+//!
+//! The scripts and their syntax trees stay in memory, a change only reparses the changed part of
+//! the script (see [`Document`]) and runs the rules on the changed files. The report of all files
+//! is redrawn after every change.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{self, IsTerminal, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{Arc, mpsc},
+    time::Duration,
+};
+
+use anyhow::Context;
+use miette::Report;
+use notify::{EventKind, RecursiveMode, Watcher, event::ModifyKind};
+
+use crate::{
+    baseline::Baseline,
+    cli,
+    config::Config,
+    document::Document,
+    files::{self, FileFilter},
+    new_parser, output, parallel, run_checks,
+    summary::{ExitStatus, Summary},
+};
+
+/// How long to wait for more events after a change, editors often save a file in several steps.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+struct WatchedFile {
+    document: Document,
+    /// The reports that aren't in the baseline.
+    reports: Vec<Report>,
+}
+
+fn read(path: &Path) -> anyhow::Result<Arc<str>> {
+    std::fs::read_to_string(path)
+        .map(Arc::<str>::from)
+        .with_context(|| format!("failed to read {}", path.display()))
+}
+
+fn check(config: &Config, path: &Path, document: &Document) -> Vec<Report> {
+    let rules = config.rules_for(path);
+    run_checks(
+        document.tree.root_node(),
+        Arc::clone(&document.source),
+        &rules,
+        path,
+    )
+}
+
+struct Watch<'a> {
+    cli: &'a cli::Cli,
+    args: &'a cli::LintArgs,
+    config: Config,
+    baseline: Option<Baseline>,
+    filter: FileFilter,
+    cwd: PathBuf,
+    /// Kept for the whole session, only the first run is parallel.
+    parser: tree_sitter::Parser,
+    /// By the path as it's collected from the arguments.
+    files: BTreeMap<PathBuf, anyhow::Result<WatchedFile>>,
+}
+
+impl Watch<'_> {
+    fn filter_baseline(&mut self, path: &Path, source: &str, reports: Vec<Report>) -> Vec<Report> {
+        match &mut self.baseline {
+            Some(baseline) => baseline.filter(path, source, reports),
+            None => reports,
+        }
+    }
+
+    /// Lints all files, with a parser per job.
+    fn lint_all(&mut self, files: &[PathBuf], jobs: NonZeroUsize) {
+        let config = &self.config;
+        let mut linted = Vec::new();
+        parallel::map_ordered(
+            files,
+            jobs,
+            || new_parser().expect("the parser was created before"),
+            |parser, path| {
+                let document = Document::parse(parser, read(path)?)?;
+                let reports = check(config, path, &document);
+                anyhow::Ok((document, reports))
+            },
+            |path, result| linted.push((path.clone(), result)),
+        );
+        for (path, result) in linted {
+            let file = result.map(|(document, reports)| WatchedFile {
+                reports: self.filter_baseline(&path, &document.source, reports),
+                document,
+            });
+            self.files.insert(path, file);
+        }
+    }
+
+    /// Whether an event about `path` can change the report.
+    fn is_relevant(&self, kind: &EventKind, path: &Path) -> bool {
+        // Reading the files to lint them causes access events
+        if matches!(kind, EventKind::Access(_)) {
+            return false;
+        }
+        if self.config.path.as_deref() == Some(path) {
+            return true;
+        }
+        let relative = path.strip_prefix(&self.cwd).unwrap_or(path);
+        match path.extension() {
+            Some(extension) => extension == "gd" && self.filter.is_included(relative),
+            // Directories with scripts in them can be created, removed or renamed
+            None => matches!(
+                kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_))
+            ),
+        }
+    }
+
+    /// Lints new and changed files again, returns whether the report changed.
+    fn update(&mut self, changed: &HashSet<PathBuf>) -> bool {
+        let mut config_changed = false;
+        if let Some(path) = &self.config.path
+            && changed.contains(path)
+        {
+            match crate::find_config(self.cli, &self.args.files) {
+                Ok(config) => {
+                    self.config = config;
+                    config_changed = true;
+                }
+                Err(err) => tracing::error!("{err:#}"),
+            }
+        }
+
+        let paths = match files::collect_files(&self.args.files) {
+            Ok(paths) => paths,
+            Err(err) => {
+                tracing::error!("{err:#}");
+                return false;
+            }
+        };
+
+        let mut old_files = std::mem::take(&mut self.files);
+        let mut updated = false;
+        for path in paths {
+            let is_changed = changed.contains(&self.cwd.join(&path));
+            let file = match old_files.remove(&path) {
+                Some(Ok(file)) if !is_changed && !config_changed => Ok(file),
+                Some(Err(err)) if !is_changed => Err(err),
+                Some(Ok(file)) => match self.relint(&path, file, config_changed) {
+                    Ok((file, relinted)) => {
+                        updated |= relinted;
+                        Ok(file)
+                    }
+                    Err(err) => {
+                        updated = true;
+                        Err(err)
+                    }
+                },
+                Some(Err(_)) | None => {
+                    updated = true;
+                    self.lint(&path)
+                }
+            };
+            self.files.insert(path, file);
+        }
+        // Removed files
+        updated || !old_files.is_empty()
+    }
+
+    fn lint(&mut self, path: &Path) -> anyhow::Result<WatchedFile> {
+        let document = Document::parse(&mut self.parser, read(path)?)?;
+        let reports = check(&self.config, path, &document);
+        Ok(WatchedFile {
+            reports: self.filter_baseline(path, &document.source, reports),
+            document,
+        })
+    }
+
+    /// Reparses a file incrementally, returns whether its reports could have changed.
+    fn relint(
+        &mut self,
+        path: &Path,
+        mut file: WatchedFile,
+        config_changed: bool,
+    ) -> anyhow::Result<(WatchedFile, bool)> {
+        let source_changed = file.document.update(&mut self.parser, read(path)?)?;
+        if !source_changed && !config_changed {
+            return Ok((file, false));
+        }
+        let reports = check(&self.config, path, &file.document);
+        file.reports = self.filter_baseline(path, &file.document.source, reports);
+        Ok((file, true))
+    }
+
+    /// Clears the terminal and prints the report of all files.
+    fn render(&self) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        if stdout.is_terminal() {
+            // Clear the screen and the scrollback, then move the cursor to the top left
+            write!(stdout, "\x1b[2J\x1b[3J\x1b[H")?;
+        }
+
+        let mut emitter = output::emitter(self.args.format, &mut stdout);
+        let mut summary = Summary::default();
+        for (path, file) in &self.files {
+            match file {
+                Ok(file) => {
+                    emitter.file(path, &file.document.source, &file.reports)?;
+                    summary.record(&file.reports);
+                }
+                Err(err) => {
+                    tracing::error!("{err:#}");
+                    summary.record_failure();
+                }
+            }
+        }
+        emitter.finish()?;
+
+        summary.print(true);
+        eprintln!("Watching for changes, press Ctrl+C to stop.");
+        Ok(())
+    }
+}
+
+/// The directories to watch for the path arguments.
+fn watched_paths(args: &cli::FileArgs) -> Vec<(PathBuf, RecursiveMode)> {
+    args.paths
+        .iter()
+        .map(|arg| {
+            let path = Path::new(arg);
+            if path.is_file() {
+                // Editors often replace files instead of writing them, that ends a watch on the
+                // file itself
+                let parent = path.parent().unwrap_or(Path::new(""));
+                (parent.to_path_buf(), RecursiveMode::NonRecursive)
+            } else if files::is_glob(arg) {
+                (files::glob_base(arg), RecursiveMode::Recursive)
+            } else {
+                (path.to_path_buf(), RecursiveMode::Recursive)
+            }
+        })
+        .collect()
+}
+
+/// Lints the files, then keeps linting them again when they change until the process is
+/// stopped.
+pub fn watch(
+    cli: &cli::Cli,
+    args: &cli::LintArgs,
+    config: Config,
+    files: &[PathBuf],
+    baseline: Option<Baseline>,
+    jobs: NonZeroUsize,
+) -> anyhow::Result<ExitStatus> {
+    let cwd = std::env::current_dir().context("getting the working directory")?;
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).context("failed to watch the files")?;
+    let config_dir = config
+        .path
+        .as_deref()
+        .and_then(Path::parent)
+        .map(|dir| (dir.to_path_buf(), RecursiveMode::NonRecursive));
+    for (path, mode) in watched_paths(&args.files).into_iter().chain(config_dir) {
+        let path = std::path::absolute(path).context("getting the working directory")?;
+        watcher
+            .watch(&path, mode)
+            .with_context(|| format!("failed to watch {}", path.display()))?;
+    }
+
+    let mut watch = Watch {
+        cli,
+        args,
+        config,
+        baseline,
+        filter: FileFilter::new(&args.files)?,
+        cwd,
+        parser: new_parser()?,
+        files: BTreeMap::new(),
+    };
+    watch.lint_all(files, jobs);
+    watch.render().context("failed to write the diagnostics")?;
+
+    loop {
+        // Collect the events of a change, until the files are quiet for a moment
+        let mut changed = HashSet::new();
+        let mut event = receiver.recv().context("the file watcher stopped")?;
+        loop {
+            match event {
+                Ok(event) => {
+                    for path in event.paths {
+                        if watch.is_relevant(&event.kind, &path) {
+                            changed.insert(path);
+                        }
+                    }
+                }
+                Err(err) => tracing::warn!("{err}"),
+            }
+            match receiver.recv_timeout(DEBOUNCE) {
+                Ok(next) => event = next,
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(err) => return Err(err).context("the file watcher stopped"),
+            }
+        }
+
+        if !changed.is_empty() && watch.update(&changed) {
+            watch.render().context("failed to write the diagnostics")?;
+        }
+    }
+}